@@ -0,0 +1,791 @@
+use crate::{
+    convert::{from_vn_value, VnValueError},
+    format::value_to_text,
+    script::{VnResult, VnValue},
+};
+use intuicio_essentials::{core::meta::Meta, prelude::*};
+use serde::de::DeserializeOwned;
+use std::{
+    collections::HashMap,
+    fmt::{Display, Formatter},
+};
+
+/// Meta key of action param that holds its [`VnParamType`].
+pub const VN_PARAM_TYPE_META: &str = "vn_type";
+pub const VN_PARAM_REQUIRED_META: &str = "vn_required";
+/// Meta key of action param that holds its default value encoded as JSON.
+pub const VN_PARAM_DEFAULT_META: &str = "vn_default";
+/// Meta key of action or its param that holds its docs.
+pub const VN_DOC_META: &str = "vn_doc";
+
+/// Type of action param that scripts are checked against.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VnParamType {
+    Any,
+    Boolean,
+    Integer,
+    Number,
+    Text,
+    Color,
+    Vec2,
+    Rect,
+    Reference,
+    /// Accepts `none` too, which missing params evaluate to.
+    Optional(Box<Self>),
+    Array(Box<Self>),
+    Map(Box<Self>),
+}
+
+impl VnParamType {
+    pub fn accepts(&self, value: &VnValue) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Boolean => value.as_boolean().is_some(),
+            Self::Integer => value.as_integer().is_some(),
+            Self::Number => value.as_number().is_some(),
+            Self::Text => matches!(value, VnValue::Text(_) | VnValue::Reference { .. }),
+            Self::Color => value.as_color().is_some(),
+            Self::Vec2 => value.as_vec2().is_some(),
+            Self::Rect => value.as_rect().is_some(),
+            Self::Reference => value.as_reference().is_some(),
+            Self::Optional(item) => value.is_none() || item.accepts(value),
+            Self::Array(item) => value
+                .as_array()
+                .map(|items| items.iter().all(|value| item.accepts(value)))
+                .unwrap_or_default(),
+            Self::Map(item) => value
+                .as_map()
+                .map(|items| items.values().all(|value| item.accepts(value)))
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Type declared by action function param, if function has typed params.
+    pub fn of_param(param: &FunctionParameter) -> Option<Self> {
+        Self::from_meta(param.meta.as_ref()?.as_map()?.get(VN_PARAM_TYPE_META)?)
+    }
+
+    pub fn to_meta(&self) -> Meta {
+        let wrap = |key: &str, item: &Self| {
+            Meta::Map(HashMap::from_iter([(key.to_owned(), item.to_meta())]))
+        };
+        match self {
+            Self::Any => Meta::Identifier("any".to_owned()),
+            Self::Boolean => Meta::Identifier("boolean".to_owned()),
+            Self::Integer => Meta::Identifier("integer".to_owned()),
+            Self::Number => Meta::Identifier("number".to_owned()),
+            Self::Text => Meta::Identifier("text".to_owned()),
+            Self::Color => Meta::Identifier("color".to_owned()),
+            Self::Vec2 => Meta::Identifier("vec2".to_owned()),
+            Self::Rect => Meta::Identifier("rect".to_owned()),
+            Self::Reference => Meta::Identifier("reference".to_owned()),
+            Self::Optional(item) => wrap("optional", item),
+            Self::Array(item) => wrap("array", item),
+            Self::Map(item) => wrap("map", item),
+        }
+    }
+
+    pub fn from_meta(meta: &Meta) -> Option<Self> {
+        match meta {
+            Meta::Identifier(name) => match name.as_str() {
+                "any" => Some(Self::Any),
+                "boolean" => Some(Self::Boolean),
+                "integer" => Some(Self::Integer),
+                "number" => Some(Self::Number),
+                "text" => Some(Self::Text),
+                "color" => Some(Self::Color),
+                "vec2" => Some(Self::Vec2),
+                "rect" => Some(Self::Rect),
+                "reference" => Some(Self::Reference),
+                _ => None,
+            },
+            Meta::Map(items) if items.len() == 1 => {
+                let (key, item) = items.iter().next()?;
+                let item = Box::new(Self::from_meta(item)?);
+                match key.as_str() {
+                    "optional" => Some(Self::Optional(item)),
+                    "array" => Some(Self::Array(item)),
+                    "map" => Some(Self::Map(item)),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Display for VnParamType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Any => write!(f, "any"),
+            Self::Boolean => write!(f, "boolean"),
+            Self::Integer => write!(f, "integer"),
+            Self::Number => write!(f, "number"),
+            Self::Text => write!(f, "text"),
+            Self::Color => write!(f, "color"),
+            Self::Vec2 => write!(f, "vec2"),
+            Self::Rect => write!(f, "rect"),
+            Self::Reference => write!(f, "reference"),
+            Self::Optional(item) => write!(f, "optional {}", item),
+            Self::Array(item) => write!(f, "array of {}", item),
+            Self::Map(item) => write!(f, "map of {}", item),
+        }
+    }
+}
+
+/// Rust type that action params get converted into.
+///
+/// Custom serde types can implement it with defaults, which makes them accept any value
+/// in validation and convert with [`from_vn_value`].
+pub trait VnParam: DeserializeOwned {
+    fn param_type() -> VnParamType {
+        VnParamType::Any
+    }
+
+    fn from_param(value: &VnValue) -> Result<Self, VnValueError> {
+        from_vn_value(value)
+    }
+}
+
+impl VnParam for VnValue {
+    fn from_param(value: &VnValue) -> Result<Self, VnValueError> {
+        Ok(value.to_owned())
+    }
+}
+
+macro_rules! impl_param {
+    ($param_type:ident => $($type:ty),+) => {
+        $(
+            impl VnParam for $type {
+                fn param_type() -> VnParamType {
+                    VnParamType::$param_type
+                }
+            }
+        )+
+    };
+}
+
+impl_param!(Boolean => bool);
+impl_param!(Integer => i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+impl_param!(Number => f32, f64);
+impl_param!(Text => String);
+
+impl<T: VnParam> VnParam for Option<T> {
+    fn param_type() -> VnParamType {
+        VnParamType::Optional(Box::new(T::param_type()))
+    }
+
+    fn from_param(value: &VnValue) -> Result<Self, VnValueError> {
+        if value.is_none() {
+            Ok(None)
+        } else {
+            T::from_param(value).map(Some)
+        }
+    }
+}
+
+impl<T: VnParam> VnParam for Vec<T> {
+    fn param_type() -> VnParamType {
+        VnParamType::Array(Box::new(T::param_type()))
+    }
+
+    fn from_param(value: &VnValue) -> Result<Self, VnValueError> {
+        value
+            .as_array()
+            .ok_or_else(|| VnValueError::new("", "expected array"))?
+            .iter()
+            .enumerate()
+            .map(|(index, value)| {
+                T::from_param(value).map_err(|error| error.within(&index.to_string()))
+            })
+            .collect()
+    }
+}
+
+impl<T: VnParam> VnParam for HashMap<String, T> {
+    fn param_type() -> VnParamType {
+        VnParamType::Map(Box::new(T::param_type()))
+    }
+
+    fn from_param(value: &VnValue) -> Result<Self, VnValueError> {
+        value
+            .as_map()
+            .ok_or_else(|| VnValueError::new("", "expected map"))?
+            .iter()
+            .map(|(key, value)| {
+                T::from_param(value)
+                    .map(|value| (key.to_owned(), value))
+                    .map_err(|error| error.within(key))
+            })
+            .collect()
+    }
+}
+
+/// Function taking context and typed params, that can be registered as action.
+pub trait VnActionFunction<Args>: 'static {
+    fn param_types() -> Vec<VnParamType>;
+
+    /// Values and names are given in order of function params.
+    fn call(
+        &self,
+        context: &mut Context,
+        values: &[VnValue],
+        names: &[String],
+    ) -> Result<VnResult, VnValueError>;
+}
+
+macro_rules! impl_action_function {
+    ($($type:ident),*) => {
+        impl<Func, $($type),*> VnActionFunction<($($type,)*)> for Func
+        where
+            Func: Fn(&mut Context, $($type),*) -> VnResult + 'static,
+            $($type: VnParam,)*
+        {
+            fn param_types() -> Vec<VnParamType> {
+                vec![$($type::param_type()),*]
+            }
+
+            #[allow(non_snake_case, unused_variables, unused_mut)]
+            fn call(
+                &self,
+                context: &mut Context,
+                values: &[VnValue],
+                names: &[String],
+            ) -> Result<VnResult, VnValueError> {
+                let mut params = values.iter().zip(names);
+                $(
+                    let (value, name) = params.next().unwrap();
+                    let $type = $type::from_param(value).map_err(|error| error.within(name))?;
+                )*
+                Ok(self(context, $($type),*))
+            }
+        }
+    };
+}
+
+impl_action_function!();
+impl_action_function!(A);
+impl_action_function!(A, B);
+impl_action_function!(A, B, C);
+impl_action_function!(A, B, C, D);
+impl_action_function!(A, B, C, D, E);
+impl_action_function!(A, B, C, D, E, F);
+impl_action_function!(A, B, C, D, E, F, G);
+impl_action_function!(A, B, C, D, E, F, G, H);
+
+/// Declared action param, read back from registry with [`VnActionParam::of_param`].
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct VnActionParam {
+    pub name: String,
+    /// Known only for params of typed actions.
+    pub param_type: Option<VnParamType>,
+    /// Scripts missing required param are rejected by [`VnStory::validate_actions`].
+    ///
+    /// [`VnStory::validate_actions`]: crate::script::VnStory::validate_actions
+    pub required: bool,
+    /// Value passed into function when script does not provide one.
+    pub default: Option<VnValue>,
+    pub doc: Option<String>,
+}
+
+impl VnActionParam {
+    pub fn new(name: impl ToString) -> Self {
+        Self {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn required(mut self) -> Self {
+        self.required = true;
+        self
+    }
+
+    pub fn default(mut self, value: VnValue) -> Self {
+        self.default = Some(value);
+        self
+    }
+
+    pub fn doc(mut self, value: impl ToString) -> Self {
+        self.doc = Some(value.to_string());
+        self
+    }
+
+    pub fn of_param(param: &FunctionParameter) -> Self {
+        let meta = param.meta.as_ref().and_then(|meta| meta.as_map());
+        let get = |key: &str| meta.and_then(|meta| meta.get(key));
+        Self {
+            name: param.name.to_owned(),
+            param_type: get(VN_PARAM_TYPE_META).and_then(VnParamType::from_meta),
+            required: get(VN_PARAM_REQUIRED_META)
+                .and_then(|meta| meta.as_value()?.as_bool())
+                .unwrap_or_default(),
+            default: get(VN_PARAM_DEFAULT_META)
+                .and_then(|meta| serde_json::from_str(meta.as_value()?.as_str()?).ok()),
+            doc: get(VN_DOC_META).and_then(|meta| meta.as_value()?.as_string()),
+        }
+    }
+
+    fn apply_meta(&self, param: &mut FunctionParameter) {
+        let mut meta = match param.meta.take() {
+            Some(Meta::Map(meta)) => meta,
+            _ => HashMap::default(),
+        };
+        if let Some(param_type) = self.param_type.as_ref() {
+            meta.insert(VN_PARAM_TYPE_META.to_owned(), param_type.to_meta());
+        }
+        if self.required {
+            meta.insert(VN_PARAM_REQUIRED_META.to_owned(), Meta::Value(true.into()));
+        }
+        if let Some(default) = self.default.as_ref() {
+            let default = serde_json::to_string(default).unwrap();
+            meta.insert(
+                VN_PARAM_DEFAULT_META.to_owned(),
+                Meta::Value(default.as_str().into()),
+            );
+        }
+        if let Some(doc) = self.doc.as_ref() {
+            meta.insert(VN_DOC_META.to_owned(), Meta::Value(doc.as_str().into()));
+        }
+        param.meta = Some(Meta::Map(meta));
+    }
+}
+
+impl From<&str> for VnActionParam {
+    fn from(name: &str) -> Self {
+        Self::new(name)
+    }
+}
+
+impl Display for VnActionParam {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)?;
+        if let Some(param_type) = self.param_type.as_ref() {
+            write!(f, ": {}", param_type)?;
+        }
+        if self.required {
+            write!(f, " (required)")?;
+        }
+        if let Some(default) = self.default.as_ref() {
+            write!(f, " = {}", value_to_text(default))?;
+        }
+        if let Some(doc) = self.doc.as_ref() {
+            write!(f, " - {}", doc)?;
+        }
+        Ok(())
+    }
+}
+
+/// Action found in registry, for tools listing what scripts can use.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VnActionInfo {
+    pub name: String,
+    pub module_name: Option<String>,
+    pub doc: Option<String>,
+    pub params: Vec<VnActionParam>,
+}
+
+impl VnActionInfo {
+    /// Functions with single `VnResult` output are considered actions.
+    pub fn of_function(function: &Function) -> Option<Self> {
+        let signature = function.signature();
+        if signature.outputs.len() != 1
+            || signature.outputs[0].struct_handle.type_hash() != TypeHash::of::<VnResult>()
+        {
+            return None;
+        }
+        Some(Self {
+            name: signature.name.to_owned(),
+            module_name: signature.module_name.to_owned(),
+            doc: signature
+                .meta
+                .as_ref()
+                .and_then(|meta| meta.as_map()?.get(VN_DOC_META)?.as_value()?.as_string()),
+            params: signature
+                .inputs
+                .iter()
+                .map(VnActionParam::of_param)
+                .collect(),
+        })
+    }
+
+    /// All actions in registry, sorted by their path.
+    pub fn all(registry: &Registry) -> Vec<Self> {
+        let mut result = registry
+            .functions()
+            .filter_map(|function| Self::of_function(function))
+            .collect::<Vec<_>>();
+        result.sort_by_key(|info| info.path());
+        result
+    }
+
+    pub fn path(&self) -> String {
+        match self.module_name.as_ref() {
+            Some(module_name) => format!("{}::{}", module_name, self.name),
+            None => self.name.to_owned(),
+        }
+    }
+}
+
+impl Display for VnActionInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.path())?;
+        if let Some(doc) = self.doc.as_ref() {
+            write!(f, " - {}", doc)?;
+        }
+        for param in &self.params {
+            write!(f, "\n    {}", param)?;
+        }
+        Ok(())
+    }
+}
+
+/// Defines action function with typed params, converted from script values before call.
+///
+/// ```ignore
+/// registry.add_function(
+///     VnActionDefinition::new("wait")
+///         .module_name("vn")
+///         .param(VnActionParam::new("seconds").required())
+///         .param(VnActionParam::new("skippable").default(VnValue::Boolean(true)))
+///         .build(registry, |context: &mut Context, seconds: f64, skippable: bool| {
+///             ...
+///         }),
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct VnActionDefinition {
+    name: String,
+    module_name: Option<String>,
+    doc: Option<String>,
+    params: Vec<VnActionParam>,
+}
+
+impl VnActionDefinition {
+    pub fn new(name: impl ToString) -> Self {
+        Self {
+            name: name.to_string(),
+            module_name: None,
+            doc: None,
+            params: vec![],
+        }
+    }
+
+    pub fn module_name(mut self, value: impl ToString) -> Self {
+        self.module_name = Some(value.to_string());
+        self
+    }
+
+    pub fn doc(mut self, value: impl ToString) -> Self {
+        self.doc = Some(value.to_string());
+        self
+    }
+
+    /// Params are matched with function params in order.
+    pub fn param(mut self, param: impl Into<VnActionParam>) -> Self {
+        self.params.push(param.into());
+        self
+    }
+
+    fn path(&self) -> String {
+        match self.module_name.as_ref() {
+            Some(module_name) => format!("{}::{}", module_name, self.name),
+            None => self.name.to_owned(),
+        }
+    }
+
+    fn signature_meta(&self) -> Option<Meta> {
+        self.doc.as_ref().map(|doc| {
+            Meta::Map(HashMap::from_iter([(
+                VN_DOC_META.to_owned(),
+                Meta::Value(doc.as_str().into()),
+            )]))
+        })
+    }
+
+    /// Params of invalid type make action panic, so stories using it have to pass
+    /// [`VnStory::validate_actions`] first, which [`Vm::add_story`] does.
+    ///
+    /// [`VnStory::validate_actions`]: crate::script::VnStory::validate_actions
+    /// [`Vm::add_story`]: crate::vm::Vm::add_story
+    pub fn build<Args, F>(self, registry: &Registry, function: F) -> Function
+    where
+        F: VnActionFunction<Args>,
+    {
+        let path = self.path();
+        let types = F::param_types();
+        if types.len() != self.params.len() {
+            panic!(
+                "Action {} declares {} params but its function takes {}!",
+                path,
+                self.params.len(),
+                types.len()
+            );
+        }
+        let value_handle = registry
+            .find_struct(StructQuery::of::<VnValue>())
+            .expect("`VnValue` is not registered!");
+        let result_handle = registry
+            .find_struct(StructQuery::of::<VnResult>())
+            .expect("`VnResult` is not registered!");
+        let mut signature = FunctionSignature::new(&self.name)
+            .with_output(FunctionParameter::new("result", result_handle));
+        signature.meta = self.signature_meta();
+        signature.module_name = self.module_name;
+        for (param, param_type) in self.params.iter().zip(types) {
+            let mut result = FunctionParameter::new(&param.name, value_handle.clone());
+            VnActionParam {
+                param_type: Some(param_type),
+                ..param.to_owned()
+            }
+            .apply_meta(&mut result);
+            signature.inputs.push(result);
+        }
+        let (names, defaults): (Vec<_>, Vec<_>) = self
+            .params
+            .into_iter()
+            .map(|param| (param.name, param.default))
+            .unzip();
+        Function::new(
+            signature,
+            FunctionBody::closure(move |context, _| {
+                let values = pop_with_defaults(context, &defaults);
+                let result = function
+                    .call(context, &values, &names)
+                    .unwrap_or_else(|error| {
+                        panic!(
+                            "Action {} got invalid param {} in not validated story!",
+                            path, error
+                        )
+                    });
+                context.stack().push(result);
+            }),
+        )
+    }
+
+    /// Attaches docs and params metadata to already defined action function,
+    /// like ones made with `intuicio_function` macro.
+    pub fn annotate(self, function: Function) -> Function {
+        let mut signature = function.signature().to_owned();
+        if signature.name != self.name || signature.module_name != self.module_name {
+            panic!(
+                "Action {} cannot annotate function {}!",
+                self.path(),
+                signature
+            );
+        }
+        signature.meta = self.signature_meta();
+        for param in &self.params {
+            let Some(input) = signature
+                .inputs
+                .iter_mut()
+                .find(|input| input.name == param.name)
+            else {
+                panic!("Action {} has no param `{}`!", self.path(), param.name);
+            };
+            param.apply_meta(input);
+        }
+        let defaults = signature
+            .inputs
+            .iter()
+            .map(|input| {
+                self.params
+                    .iter()
+                    .find(|param| param.name == input.name)
+                    .and_then(|param| param.default.to_owned())
+            })
+            .collect::<Vec<_>>();
+        let defaults = defaults.iter().any(Option::is_some).then_some(defaults);
+        Function::new(
+            signature,
+            FunctionBody::closure(move |context, registry| {
+                if let Some(defaults) = defaults.as_ref() {
+                    let values = pop_with_defaults(context, defaults);
+                    for value in values.into_iter().rev() {
+                        context.stack().push(value);
+                    }
+                }
+                function.invoke(context, registry)
+            }),
+        )
+    }
+}
+
+/// Pops action params in declaration order, replacing missing ones with defaults
+/// decoded once when action got defined.
+fn pop_with_defaults(context: &mut Context, defaults: &[Option<VnValue>]) -> Vec<VnValue> {
+    defaults
+        .iter()
+        .map(|default| {
+            let value = context.stack().pop::<VnValue>().unwrap();
+            match default {
+                Some(default) if value.is_none() => default.to_owned(),
+                _ => value,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{script::*, vm::*};
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize)]
+    struct Position {
+        x: f64,
+        y: f64,
+    }
+
+    impl VnParam for Position {}
+
+    fn place(
+        context: &mut Context,
+        name: String,
+        position: Position,
+        tags: Option<Vec<String>>,
+    ) -> VnResult {
+        let globals = context
+            .custom_mut::<Globals>(VN_GLOBALS)
+            .expect("Cannot access VN globals!");
+        globals.properties.insert(
+            name,
+            VnValue::Vec2 {
+                x: position.x,
+                y: position.y,
+            },
+        );
+        globals.properties.insert(
+            "tags".to_owned(),
+            VnValue::Integer(tags.unwrap_or_default().len() as _),
+        );
+        VnResult::Continue
+    }
+
+    #[test]
+    fn test_action() {
+        let param_type =
+            VnParamType::Optional(Box::new(VnParamType::Array(Box::new(VnParamType::Number))));
+        assert_eq!(
+            VnParamType::from_meta(&param_type.to_meta()),
+            Some(param_type.clone())
+        );
+        assert_eq!(param_type.to_string(), "optional array of number");
+        assert!(param_type.accepts(&VnValue::None));
+        assert!(param_type.accepts(&VnValue::Array(vec![VnValue::Integer(1)])));
+        assert!(!param_type.accepts(&VnValue::Array(vec![VnValue::Boolean(true)])));
+
+        let mut registry = Registry::default().with_basic_types();
+        crate::library::install(&mut registry);
+        let function = VnActionDefinition::new("place")
+            .module_name("test")
+            .param("name")
+            .param("position")
+            .param("tags")
+            .build(&registry, place);
+        registry.add_function(function);
+        let story = VnFile::parse(
+            r#"
+            chapter main {
+                place name: rin position: { x: 1 y: 2.5 } tags: [a b]
+                place position: 4 tags: [1]
+                unknown
+            }
+            "#,
+        )
+        .unwrap()
+        .story;
+        assert_eq!(
+            story.validate_actions(&registry),
+            Err(vec![
+                "chapter main item 1 name: expected text".to_owned(),
+                "chapter main item 1 tags: expected optional array of text".to_owned(),
+                "chapter main item 2: unknown action `unknown`".to_owned(),
+            ])
+        );
+
+        let host = Host::new(Context::new(1024, 1024, 1024), registry.into());
+        let mut vm = Vm::new(host);
+        assert!(vm.add_story(&story).is_err());
+        let story = VnFile::parse(
+            r#"
+            chapter main {
+                place name: rin position: { x: 1 y: 2.5 } tags: [a b]
+            }
+            "#,
+        )
+        .unwrap()
+        .story;
+        vm.add_story(&story).unwrap();
+        vm.enter("main", None);
+        vm.step();
+        assert_eq!(
+            vm.globals().properties.get("rin"),
+            Some(&VnValue::Vec2 { x: 1.0, y: 2.5 })
+        );
+        assert_eq!(
+            vm.globals().properties.get("tags"),
+            Some(&VnValue::Integer(2))
+        );
+    }
+
+    #[test]
+    fn test_action_params() {
+        let mut registry = Registry::default().with_basic_types();
+        crate::library::install(&mut registry);
+        crate::harness::install(&mut registry);
+        let story = VnFile::parse(
+            r#"
+            chapter main {
+                wait
+                vn_dialog.say who: rin
+                jump_random labels: [a b] weights: [1]
+                jump_random labels: [a b] weights: [1 3]
+                wait seconds: 1 skipable: false
+            }
+
+            chapter roll {
+                random name: roll
+            }
+            "#,
+        )
+        .unwrap()
+        .story;
+        assert_eq!(
+            story.validate_actions(&registry),
+            Err(vec![
+                "chapter main item 0 seconds: missing required param".to_owned(),
+                "chapter main item 1 what: missing required param".to_owned(),
+                "chapter main item 2 weights: expected 2 items, one per label".to_owned(),
+                "chapter main item 4 skipable: unknown param".to_owned(),
+            ])
+        );
+
+        let actions = VnActionInfo::all(&registry);
+        let wait = actions
+            .iter()
+            .find(|action| action.path() == "vn::wait")
+            .unwrap();
+        assert_eq!(
+            wait.to_string(),
+            "vn::wait - Suspends story for given amount of story time.\n    \
+            seconds: number (required)\n    \
+            skippable: boolean = true"
+        );
+        let jump = actions
+            .iter()
+            .find(|action| action.path() == "vn::jump")
+            .unwrap();
+        assert!(jump.doc.is_some());
+        assert_eq!(jump.params[0].param_type, None);
+
+        let host = Host::new(Context::new(1024, 1024, 1024), registry.into());
+        let mut vm = Vm::new(host);
+        vm.add_chapter("roll", story.chapters.get("roll").unwrap().to_owned());
+        vm.enter("roll", None);
+        vm.step();
+        let roll = vm.globals().properties.get("roll").unwrap().as_integer();
+        assert!(matches!(roll, Some(0 | 1)));
+    }
+}
@@ -0,0 +1,1205 @@
+use crate::script::{VnReferenceKind, VnValue};
+use serde::{
+    de::{
+        self,
+        value::{BorrowedStrDeserializer, MapDeserializer, SeqDeserializer},
+        DeserializeSeed, Visitor,
+    },
+    ser, Deserialize, Serialize,
+};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::{Display, Formatter},
+};
+
+/// Conversion error with path to value that caused it, like `character.rin.position.x`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VnValueError {
+    pub path: String,
+    pub message: String,
+}
+
+impl VnValueError {
+    pub(crate) fn new(path: &str, message: impl ToString) -> Self {
+        Self {
+            path: path.to_owned(),
+            message: message.to_string(),
+        }
+    }
+
+    /// Errors reported by visitors know nothing about path, so it gets filled by
+    /// closest deserializer they came through.
+    fn at(mut self, path: &str) -> Self {
+        if self.path.is_empty() {
+            self.path = path.to_owned();
+        }
+        self
+    }
+
+    /// Prefixes path with key of value that contains it.
+    pub(crate) fn within(mut self, key: &str) -> Self {
+        self.path = if self.path.is_empty() {
+            key.to_owned()
+        } else {
+            format!("{}.{}", key, self.path)
+        };
+        self
+    }
+}
+
+impl Display for VnValueError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.path, self.message)
+        }
+    }
+}
+
+impl Error for VnValueError {}
+
+impl de::Error for VnValueError {
+    fn custom<T: Display>(message: T) -> Self {
+        Self::new("", message)
+    }
+}
+
+impl ser::Error for VnValueError {
+    fn custom<T: Display>(message: T) -> Self {
+        Self::new("", message)
+    }
+}
+
+/// Deserializes Rust type from value.
+pub fn from_vn_value<'de, T>(value: &'de VnValue) -> Result<T, VnValueError>
+where
+    T: Deserialize<'de>,
+{
+    T::deserialize(VnValueDeserializer::new(value, ""))
+}
+
+/// Deserializes Rust type from properties of config, character or scene,
+/// errors being reported relative to given path.
+pub fn from_vn_properties<'de, T>(
+    path: &str,
+    properties: &'de HashMap<String, VnValue>,
+) -> Result<T, VnValueError>
+where
+    T: Deserialize<'de>,
+{
+    T::deserialize(VnMapDeserializer::new(properties, path)).map_err(|error| error.at(path))
+}
+
+/// Serializes Rust type into value.
+pub fn to_vn_value<T>(value: &T) -> Result<VnValue, VnValueError>
+where
+    T: Serialize + ?Sized,
+{
+    value.serialize(VnValueSerializer)
+}
+
+fn child_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_owned()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+fn variant_name(value: &VnValue) -> &'static str {
+    match value {
+        VnValue::None => "None",
+        VnValue::Boolean(_) => "Boolean",
+        VnValue::Integer(_) => "Integer",
+        VnValue::Number(_) => "Number",
+        VnValue::Text(_) => "Text",
+        VnValue::Color(_) => "Color",
+        VnValue::Vec2 { .. } => "Vec2",
+        VnValue::Rect { .. } => "Rect",
+        VnValue::Reference { .. } => "Reference",
+        VnValue::Array(_) => "Array",
+        VnValue::Map(_) => "Map",
+    }
+}
+
+fn reference_kind_name(kind: VnReferenceKind) -> &'static str {
+    match kind {
+        VnReferenceKind::Chapter => "Chapter",
+        VnReferenceKind::Character => "Character",
+        VnReferenceKind::Scene => "Scene",
+    }
+}
+
+/// Vectors and rectangles are seen as maps of their fields.
+fn fields(value: &VnValue) -> Option<Vec<(&'static str, f64)>> {
+    match value {
+        VnValue::Vec2 { x, y } => Some(vec![("x", *x), ("y", *y)]),
+        VnValue::Rect {
+            x,
+            y,
+            width,
+            height,
+        } => Some(vec![
+            ("x", *x),
+            ("y", *y),
+            ("width", *width),
+            ("height", *height),
+        ]),
+        _ => None,
+    }
+}
+
+/// Deserializer that reads Rust types from [`VnValue`].
+///
+/// `VnValue` fields of deserialized types receive values as they are.
+pub struct VnValueDeserializer<'de> {
+    value: &'de VnValue,
+    path: String,
+}
+
+impl<'de> VnValueDeserializer<'de> {
+    pub fn new(value: &'de VnValue, path: impl ToString) -> Self {
+        Self {
+            value,
+            path: path.to_string(),
+        }
+    }
+
+    fn expected(&self, what: &str) -> VnValueError {
+        VnValueError::new(&self.path, format!("expected {}", what))
+    }
+
+    fn deserialize_integer<V>(self, visitor: V) -> Result<V::Value, VnValueError>
+    where
+        V: Visitor<'de>,
+    {
+        let result = match self.value {
+            VnValue::Color(value) => visitor.visit_u32(*value),
+            value => match value.as_integer() {
+                Some(value) => visitor.visit_i64(value),
+                None => return Err(self.expected("integer")),
+            },
+        };
+        result.map_err(|error: VnValueError| error.at(&self.path))
+    }
+
+    fn deserialize_number<V>(self, visitor: V) -> Result<V::Value, VnValueError>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value.as_number() {
+            Some(value) => visitor
+                .visit_f64(value)
+                .map_err(|error: VnValueError| error.at(&self.path)),
+            None => Err(self.expected("number")),
+        }
+    }
+
+    fn deserialize_fields<V>(self, visitor: V) -> Result<V::Value, VnValueError>
+    where
+        V: Visitor<'de>,
+    {
+        let result = match self.value {
+            VnValue::Map(items) => visitor.visit_map(VnMapAccess::new(items, &self.path)),
+            VnValue::Reference { kind, name } => visitor.visit_map(MapDeserializer::new(
+                [
+                    ("kind", reference_kind_name(*kind)),
+                    ("name", name.as_str()),
+                ]
+                .into_iter(),
+            )),
+            value => match fields(value) {
+                Some(fields) => visitor.visit_map(MapDeserializer::new(fields.into_iter())),
+                None => return Err(self.expected("map")),
+            },
+        };
+        result.map_err(|error| error.at(&self.path))
+    }
+}
+
+impl<'de> de::Deserializer<'de> for VnValueDeserializer<'de> {
+    type Error = VnValueError;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        let result = match self.value {
+            VnValue::None => visitor.visit_unit(),
+            VnValue::Boolean(value) => visitor.visit_bool(*value),
+            VnValue::Integer(value) => visitor.visit_i64(*value),
+            VnValue::Number(value) => visitor.visit_f64(*value),
+            VnValue::Text(value) => visitor.visit_borrowed_str(value),
+            VnValue::Color(value) => visitor.visit_u32(*value),
+            VnValue::Reference { name, .. } => visitor.visit_borrowed_str(name),
+            VnValue::Array(items) => visitor.visit_seq(VnSeqAccess::new(items, &self.path)),
+            VnValue::Vec2 { .. } | VnValue::Rect { .. } | VnValue::Map(_) => {
+                return self.deserialize_fields(visitor)
+            }
+        };
+        result.map_err(|error| error.at(&self.path))
+    }
+
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value.as_boolean() {
+            Some(value) => visitor
+                .visit_bool(value)
+                .map_err(|error: VnValueError| error.at(&self.path)),
+            None => Err(self.expected("boolean")),
+        }
+    }
+
+    fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_integer(visitor)
+    }
+
+    fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_integer(visitor)
+    }
+
+    fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_integer(visitor)
+    }
+
+    fn deserialize_i64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_integer(visitor)
+    }
+
+    fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_integer(visitor)
+    }
+
+    fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_integer(visitor)
+    }
+
+    fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_integer(visitor)
+    }
+
+    fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_integer(visitor)
+    }
+
+    fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_number(visitor)
+    }
+
+    fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_number(visitor)
+    }
+
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        let result = match self.value {
+            VnValue::Text(value) => visitor.visit_borrowed_str(value),
+            VnValue::Reference { name, .. } => visitor.visit_borrowed_str(name),
+            _ => return Err(self.expected("text")),
+        };
+        result.map_err(|error: VnValueError| error.at(&self.path))
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        if self.value.is_none() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        if self.value.is_none() {
+            visitor.visit_unit()
+        } else {
+            Err(self.expected("none"))
+        }
+    }
+
+    fn deserialize_unit_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        let result = match self.value {
+            VnValue::Array(items) => visitor.visit_seq(VnSeqAccess::new(items, &self.path)),
+            value => match fields(value) {
+                Some(fields) => visitor.visit_seq(SeqDeserializer::new(
+                    fields.into_iter().map(|(_, value)| value),
+                )),
+                None => return Err(self.expected("array")),
+            },
+        };
+        result.map_err(|error| error.at(&self.path))
+    }
+
+    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_fields(visitor)
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_fields(visitor)
+    }
+
+    /// Unit variants are read from texts, other variants from maps with single entry.
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        let result = match self.value {
+            _ if name == "VnValue" => visitor.visit_enum(VnEnumAccess {
+                variant: variant_name(self.value),
+                deserializer: VnValueDeserializer::new(self.value, &self.path),
+            }),
+            VnValue::Text(variant) => visitor.visit_enum(BorrowedStrDeserializer::new(variant)),
+            VnValue::Map(items) if items.len() == 1 => {
+                let (variant, value) = items.iter().next().unwrap();
+                let path = child_path(&self.path, variant);
+                visitor.visit_enum(VnEnumAccess {
+                    variant: variant.as_str(),
+                    deserializer: VnValueDeserializer::new(value, path),
+                })
+            }
+            _ => return Err(self.expected("enum variant")),
+        };
+        result.map_err(|error| error.at(&self.path))
+    }
+
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+}
+
+/// Deserializer of properties map that is not wrapped in [`VnValue`].
+pub struct VnMapDeserializer<'de> {
+    items: &'de HashMap<String, VnValue>,
+    path: String,
+}
+
+impl<'de> VnMapDeserializer<'de> {
+    pub fn new(items: &'de HashMap<String, VnValue>, path: impl ToString) -> Self {
+        Self {
+            items,
+            path: path.to_string(),
+        }
+    }
+}
+
+impl<'de> de::Deserializer<'de> for VnMapDeserializer<'de> {
+    type Error = VnValueError;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_map(VnMapAccess::new(self.items, &self.path))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+struct VnSeqAccess<'de> {
+    items: std::slice::Iter<'de, VnValue>,
+    index: usize,
+    path: String,
+}
+
+impl<'de> VnSeqAccess<'de> {
+    fn new(items: &'de [VnValue], path: &str) -> Self {
+        Self {
+            items: items.iter(),
+            index: 0,
+            path: path.to_owned(),
+        }
+    }
+}
+
+impl<'de> de::SeqAccess<'de> for VnSeqAccess<'de> {
+    type Error = VnValueError;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        let value = match self.items.next() {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        let path = format!("{}[{}]", self.path, self.index);
+        self.index += 1;
+        seed.deserialize(VnValueDeserializer::new(value, &path))
+            .map(Some)
+            .map_err(|error| error.at(&path))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.items.len())
+    }
+}
+
+struct VnMapAccess<'de> {
+    items: std::collections::hash_map::Iter<'de, String, VnValue>,
+    value: Option<(&'de str, &'de VnValue)>,
+    path: String,
+}
+
+impl<'de> VnMapAccess<'de> {
+    fn new(items: &'de HashMap<String, VnValue>, path: &str) -> Self {
+        Self {
+            items: items.iter(),
+            value: None,
+            path: path.to_owned(),
+        }
+    }
+}
+
+impl<'de> de::MapAccess<'de> for VnMapAccess<'de> {
+    type Error = VnValueError;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        let (key, value) = match self.items.next() {
+            Some(item) => item,
+            None => return Ok(None),
+        };
+        self.value = Some((key.as_str(), value));
+        seed.deserialize(BorrowedStrDeserializer::new(key))
+            .map(Some)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let (key, value) = self
+            .value
+            .take()
+            .expect("Map value requested before its key!");
+        let path = child_path(&self.path, key);
+        seed.deserialize(VnValueDeserializer::new(value, &path))
+            .map_err(|error| error.at(&path))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.items.len())
+    }
+}
+
+struct VnEnumAccess<'de> {
+    variant: &'de str,
+    deserializer: VnValueDeserializer<'de>,
+}
+
+impl<'de> de::EnumAccess<'de> for VnEnumAccess<'de> {
+    type Error = VnValueError;
+    type Variant = VnValueDeserializer<'de>;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant), Self::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(BorrowedStrDeserializer::new(self.variant))?;
+        Ok((variant, self.deserializer))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for VnValueDeserializer<'de> {
+    type Error = VnValueError;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, Self::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_fields(visitor)
+    }
+}
+
+/// Serializer that writes Rust types into [`VnValue`].
+///
+/// Unit variants become texts, other variants become maps with single entry.
+pub struct VnValueSerializer;
+
+impl ser::Serializer for VnValueSerializer {
+    type Ok = VnValue;
+    type Error = VnValueError;
+    type SerializeSeq = VnSeqSerializer;
+    type SerializeTuple = VnSeqSerializer;
+    type SerializeTupleStruct = VnSeqSerializer;
+    type SerializeTupleVariant = VnSeqSerializer;
+    type SerializeMap = VnMapSerializer;
+    type SerializeStruct = VnMapSerializer;
+    type SerializeStructVariant = VnMapSerializer;
+
+    fn serialize_bool(self, value: bool) -> Result<Self::Ok, Self::Error> {
+        Ok(VnValue::Boolean(value))
+    }
+
+    fn serialize_i8(self, value: i8) -> Result<Self::Ok, Self::Error> {
+        Ok(VnValue::Integer(value as _))
+    }
+
+    fn serialize_i16(self, value: i16) -> Result<Self::Ok, Self::Error> {
+        Ok(VnValue::Integer(value as _))
+    }
+
+    fn serialize_i32(self, value: i32) -> Result<Self::Ok, Self::Error> {
+        Ok(VnValue::Integer(value as _))
+    }
+
+    fn serialize_i64(self, value: i64) -> Result<Self::Ok, Self::Error> {
+        Ok(VnValue::Integer(value))
+    }
+
+    fn serialize_u8(self, value: u8) -> Result<Self::Ok, Self::Error> {
+        Ok(VnValue::Integer(value as _))
+    }
+
+    fn serialize_u16(self, value: u16) -> Result<Self::Ok, Self::Error> {
+        Ok(VnValue::Integer(value as _))
+    }
+
+    fn serialize_u32(self, value: u32) -> Result<Self::Ok, Self::Error> {
+        Ok(VnValue::Integer(value as _))
+    }
+
+    fn serialize_u64(self, value: u64) -> Result<Self::Ok, Self::Error> {
+        i64::try_from(value)
+            .map(VnValue::Integer)
+            .map_err(|_| VnValueError::new("", format!("integer {} is too big", value)))
+    }
+
+    fn serialize_f32(self, value: f32) -> Result<Self::Ok, Self::Error> {
+        Ok(VnValue::Number(value as _))
+    }
+
+    fn serialize_f64(self, value: f64) -> Result<Self::Ok, Self::Error> {
+        Ok(VnValue::Number(value))
+    }
+
+    fn serialize_char(self, value: char) -> Result<Self::Ok, Self::Error> {
+        Ok(VnValue::Text(value.to_string()))
+    }
+
+    fn serialize_str(self, value: &str) -> Result<Self::Ok, Self::Error> {
+        Ok(VnValue::Text(value.to_owned()))
+    }
+
+    fn serialize_bytes(self, value: &[u8]) -> Result<Self::Ok, Self::Error> {
+        Ok(VnValue::Array(
+            value
+                .iter()
+                .map(|value| VnValue::Integer(*value as _))
+                .collect(),
+        ))
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        Ok(VnValue::None)
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok, Self::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        Ok(VnValue::None)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
+        Ok(VnValue::None)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Self::Error> {
+        if name == "VnValue" {
+            Ok(VnValue::None)
+        } else {
+            Ok(VnValue::Text(variant.to_owned()))
+        }
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let value = value.serialize(self)?;
+        if name != "VnValue" {
+            return Ok(VnValue::Map(
+                [(variant.to_owned(), value)].into_iter().collect(),
+            ));
+        }
+        match (variant, value) {
+            ("Color", VnValue::Integer(value)) => Ok(VnValue::Color(value as _)),
+            ("Number", VnValue::Integer(value)) => Ok(VnValue::Number(value as _)),
+            (_, value) => Ok(value),
+        }
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Ok(VnSeqSerializer {
+            items: Vec::with_capacity(len.unwrap_or_default()),
+            variant: None,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Ok(VnSeqSerializer {
+            items: Vec::with_capacity(len),
+            variant: Some(variant),
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Ok(VnMapSerializer::default())
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        Ok(VnMapSerializer::default())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Ok(VnMapSerializer {
+            variant: Some((name, variant)),
+            ..Default::default()
+        })
+    }
+}
+
+pub struct VnSeqSerializer {
+    items: Vec<VnValue>,
+    variant: Option<&'static str>,
+}
+
+impl VnSeqSerializer {
+    fn push<T>(&mut self, value: &T) -> Result<(), VnValueError>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.items.push(value.serialize(VnValueSerializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<VnValue, VnValueError> {
+        let result = VnValue::Array(self.items);
+        match self.variant {
+            Some(variant) => Ok(VnValue::Map(
+                [(variant.to_owned(), result)].into_iter().collect(),
+            )),
+            None => Ok(result),
+        }
+    }
+}
+
+impl ser::SerializeSeq for VnSeqSerializer {
+    type Ok = VnValue;
+    type Error = VnValueError;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for VnSeqSerializer {
+    type Ok = VnValue;
+    type Error = VnValueError;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for VnSeqSerializer {
+    type Ok = VnValue;
+    type Error = VnValueError;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for VnSeqSerializer {
+    type Ok = VnValue;
+    type Error = VnValueError;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+#[derive(Default)]
+pub struct VnMapSerializer {
+    items: HashMap<String, VnValue>,
+    key: Option<String>,
+    /// Type name and variant name of struct variant.
+    variant: Option<(&'static str, &'static str)>,
+}
+
+impl VnMapSerializer {
+    fn insert<T>(&mut self, key: &str, value: &T) -> Result<(), VnValueError>
+    where
+        T: Serialize + ?Sized,
+    {
+        let value = value
+            .serialize(VnValueSerializer)
+            .map_err(|error| error.within(key))?;
+        self.items.insert(key.to_owned(), value);
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<VnValue, VnValueError> {
+        let (name, variant) = match self.variant {
+            Some(variant) => variant,
+            None => return Ok(VnValue::Map(self.items)),
+        };
+        if name != "VnValue" {
+            return Ok(VnValue::Map(
+                [(variant.to_owned(), VnValue::Map(self.items))]
+                    .into_iter()
+                    .collect(),
+            ));
+        }
+        let mut number = |key: &str| {
+            self.items
+                .remove(key)
+                .and_then(|value| value.as_number())
+                .ok_or_else(|| VnValueError::new(key, "expected number"))
+        };
+        match variant {
+            "Vec2" => Ok(VnValue::Vec2 {
+                x: number("x")?,
+                y: number("y")?,
+            }),
+            "Rect" => Ok(VnValue::Rect {
+                x: number("x")?,
+                y: number("y")?,
+                width: number("width")?,
+                height: number("height")?,
+            }),
+            "Reference" => {
+                let kind = match self.items.get("kind").and_then(|kind| kind.as_text()) {
+                    Some("Chapter") => VnReferenceKind::Chapter,
+                    Some("Character") => VnReferenceKind::Character,
+                    Some("Scene") => VnReferenceKind::Scene,
+                    _ => return Err(VnValueError::new("kind", "expected reference kind")),
+                };
+                let name = match self.items.remove("name") {
+                    Some(VnValue::Text(name)) => name,
+                    _ => return Err(VnValueError::new("name", "expected text")),
+                };
+                Ok(VnValue::Reference { kind, name })
+            }
+            _ => Err(VnValueError::new(
+                "",
+                format!("unknown value variant `{}`", variant),
+            )),
+        }
+    }
+}
+
+impl ser::SerializeMap for VnMapSerializer {
+    type Ok = VnValue;
+    type Error = VnValueError;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Self::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.key = Some(match key.serialize(VnValueSerializer)? {
+            VnValue::Text(key) => key,
+            VnValue::Integer(key) => key.to_string(),
+            _ => return Err(VnValueError::new("", "expected text map key")),
+        });
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let key = self
+            .key
+            .take()
+            .expect("Map value serialized before its key!");
+        self.insert(&key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for VnMapSerializer {
+    type Ok = VnValue;
+    type Error = VnValueError;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for VnMapSerializer {
+    type Ok = VnValue;
+    type Error = VnValueError;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::script::VnFile;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    enum Mood {
+        Happy,
+        Sad,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Position {
+        x: f32,
+        y: f32,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Character {
+        name: String,
+        #[serde(default)]
+        variants: HashMap<String, String>,
+        position: Option<Position>,
+        mood: Mood,
+        tags: Vec<String>,
+        tint: VnValue,
+    }
+
+    #[test]
+    fn test_convert() {
+        let story = VnFile::parse(
+            r#"
+            character rin {
+                name: "Rin"
+                variants: { default: "rin.png" }
+                position: vec2(0.5, 1)
+                mood: happy
+                tags: ["friend" "main"]
+                tint: #ff8000
+            }
+
+            character kai {
+                name: "Kai"
+                position: { x: 1 y: "top" }
+                mood: sad
+                tags: ["rival"]
+                tint: none
+            }
+            "#,
+        )
+        .unwrap()
+        .story;
+        let rin = &story.characters.get("rin").unwrap().properties;
+        let character = from_vn_properties::<Character>("character.rin", rin).unwrap();
+        assert_eq!(
+            character,
+            Character {
+                name: "Rin".to_owned(),
+                variants: [("default".to_owned(), "rin.png".to_owned())]
+                    .into_iter()
+                    .collect(),
+                position: Some(Position { x: 0.5, y: 1.0 }),
+                mood: Mood::Happy,
+                tags: vec!["friend".to_owned(), "main".to_owned()],
+                tint: VnValue::Color(0xff8000ff),
+            }
+        );
+        let value = to_vn_value(&character).unwrap();
+        assert_eq!(
+            value.as_map().unwrap().get("mood"),
+            Some(&VnValue::Text("happy".to_owned()))
+        );
+        assert_eq!(from_vn_value::<Character>(&value).unwrap(), character);
+
+        let kai = &story.characters.get("kai").unwrap().properties;
+        assert_eq!(
+            from_vn_properties::<Character>("character.kai", kai)
+                .unwrap_err()
+                .to_string(),
+            "character.kai.position.y: expected number"
+        );
+        assert_eq!(
+            from_vn_value::<Vec<u8>>(&VnValue::Array(vec![
+                VnValue::Integer(1),
+                VnValue::Text("2".to_owned())
+            ]))
+            .unwrap_err()
+            .to_string(),
+            "[1]: expected integer"
+        );
+        assert_eq!(
+            from_vn_value::<Position>(&VnValue::Map(Default::default()))
+                .unwrap_err()
+                .to_string(),
+            "missing field `x`"
+        );
+    }
+}
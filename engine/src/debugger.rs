@@ -0,0 +1,427 @@
+use crate::script::{VnChapter, VnChapterItem, VnValue};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    io::{BufRead, ErrorKind, Read, Write},
+    sync::mpsc::{channel, Receiver, TryRecvError},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum VnBreakpoint {
+    /// Breaks when chapter gets executed from its beginning.
+    Chapter {
+        chapter: String,
+    },
+    Label {
+        chapter: String,
+        label: String,
+    },
+    Action {
+        chapter: String,
+        position: usize,
+    },
+}
+
+impl VnBreakpoint {
+    pub fn matches(&self, chapter_name: &str, chapter: &VnChapter, position: usize) -> bool {
+        match self {
+            Self::Chapter { chapter } => chapter == chapter_name && position == 0,
+            Self::Label {
+                chapter: breakpoint_chapter,
+                label,
+            } => {
+                breakpoint_chapter == chapter_name
+                    && matches!(
+                        chapter.items.get(position),
+                        Some(VnChapterItem::Label(name)) if name == label
+                    )
+            }
+            Self::Action {
+                chapter,
+                position: breakpoint_position,
+            } => chapter == chapter_name && *breakpoint_position == position,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VnStackFrame {
+    pub thread: String,
+    pub chapter: String,
+    pub position: usize,
+    /// Closest label at or above position.
+    pub label: Option<String>,
+}
+
+/// Debugging API of story VM.
+pub trait VnDebug {
+    fn breakpoints(&self) -> Vec<VnBreakpoint>;
+
+    fn add_breakpoint(&mut self, breakpoint: VnBreakpoint);
+
+    fn remove_breakpoint(&mut self, breakpoint: &VnBreakpoint);
+
+    fn clear_breakpoints(&mut self);
+
+    fn is_paused(&self) -> bool;
+
+    fn pause(&mut self);
+
+    fn resume(&mut self);
+
+    /// Pauses at next action of paused thread, going into entered chapters.
+    fn step_into(&mut self);
+
+    /// Pauses at next action of paused thread, skipping over entered chapters.
+    fn step_over(&mut self);
+
+    /// Pauses at next action of paused thread after current chapter exits.
+    fn step_out(&mut self);
+
+    /// Frames of all threads, top of each thread stack last.
+    fn stack(&self) -> Vec<VnStackFrame>;
+
+    fn global(&mut self, name: &str) -> Option<VnValue>;
+
+    fn global_properties(&mut self) -> HashMap<String, VnValue>;
+
+    fn set_global(&mut self, name: &str, value: VnValue);
+
+    fn delete_global(&mut self, name: &str);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Stepping {
+    Into,
+    Over,
+    Out,
+}
+
+/// Debugger state owned by VM.
+#[derive(Debug, Default)]
+pub(crate) struct DebugState {
+    pub breakpoints: Vec<VnBreakpoint>,
+    pub paused: bool,
+    /// Thread index and its stack depth at the moment of pause.
+    pub paused_at: Option<(usize, usize)>,
+    pub stepping: Option<Stepping>,
+    /// Location to not break at again right after resuming from it.
+    pub skip: Option<(usize, String, usize)>,
+}
+
+impl DebugState {
+    pub fn should_pause(
+        &mut self,
+        thread: usize,
+        depth: usize,
+        chapter_name: &str,
+        chapter: &VnChapter,
+        position: usize,
+    ) -> bool {
+        if let Some((skip_thread, skip_chapter, skip_position)) = self.skip.as_ref() {
+            if *skip_thread == thread && skip_chapter == chapter_name && *skip_position == position
+            {
+                self.skip = None;
+                return false;
+            }
+        }
+        if let (Some(stepping), Some((paused_thread, paused_depth))) =
+            (self.stepping, self.paused_at)
+        {
+            if paused_thread == thread {
+                let result = match stepping {
+                    Stepping::Into => true,
+                    Stepping::Over => depth <= paused_depth,
+                    Stepping::Out => depth < paused_depth,
+                };
+                if result {
+                    return true;
+                }
+            }
+        }
+        self.breakpoints
+            .iter()
+            .any(|breakpoint| breakpoint.matches(chapter_name, chapter, position))
+    }
+
+    pub fn pause_at(&mut self, thread: usize, depth: usize, chapter_name: &str, position: usize) {
+        self.paused = true;
+        self.paused_at = Some((thread, depth));
+        self.stepping = None;
+        self.skip = Some((thread, chapter_name.to_owned(), position));
+    }
+
+    pub fn resume(&mut self, stepping: Option<Stepping>) {
+        self.paused = false;
+        self.stepping = stepping;
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum VnDebugRequest {
+    Status,
+    Breakpoints,
+    AddBreakpoint { breakpoint: VnBreakpoint },
+    RemoveBreakpoint { breakpoint: VnBreakpoint },
+    ClearBreakpoints,
+    Pause,
+    Resume,
+    StepInto,
+    StepOver,
+    StepOut,
+    Stack,
+    Globals,
+    GetGlobal { name: String },
+    SetGlobal { name: String, value: VnValue },
+    DeleteGlobal { name: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "response", rename_all = "snake_case")]
+pub enum VnDebugResponse {
+    Ok,
+    Error {
+        message: String,
+    },
+    Status {
+        paused: bool,
+    },
+    Breakpoints {
+        breakpoints: Vec<VnBreakpoint>,
+    },
+    Stack {
+        frames: Vec<VnStackFrame>,
+    },
+    Globals {
+        properties: HashMap<String, VnValue>,
+    },
+    Global {
+        value: Option<VnValue>,
+    },
+}
+
+pub fn handle_request(debug: &mut impl VnDebug, request: VnDebugRequest) -> VnDebugResponse {
+    match request {
+        VnDebugRequest::Status => VnDebugResponse::Status {
+            paused: debug.is_paused(),
+        },
+        VnDebugRequest::Breakpoints => VnDebugResponse::Breakpoints {
+            breakpoints: debug.breakpoints(),
+        },
+        VnDebugRequest::AddBreakpoint { breakpoint } => {
+            debug.add_breakpoint(breakpoint);
+            VnDebugResponse::Ok
+        }
+        VnDebugRequest::RemoveBreakpoint { breakpoint } => {
+            debug.remove_breakpoint(&breakpoint);
+            VnDebugResponse::Ok
+        }
+        VnDebugRequest::ClearBreakpoints => {
+            debug.clear_breakpoints();
+            VnDebugResponse::Ok
+        }
+        VnDebugRequest::Pause => {
+            debug.pause();
+            VnDebugResponse::Ok
+        }
+        VnDebugRequest::Resume => {
+            debug.resume();
+            VnDebugResponse::Ok
+        }
+        VnDebugRequest::StepInto => {
+            debug.step_into();
+            VnDebugResponse::Ok
+        }
+        VnDebugRequest::StepOver => {
+            debug.step_over();
+            VnDebugResponse::Ok
+        }
+        VnDebugRequest::StepOut => {
+            debug.step_out();
+            VnDebugResponse::Ok
+        }
+        VnDebugRequest::Stack => VnDebugResponse::Stack {
+            frames: debug.stack(),
+        },
+        VnDebugRequest::Globals => VnDebugResponse::Globals {
+            properties: debug.global_properties(),
+        },
+        VnDebugRequest::GetGlobal { name } => VnDebugResponse::Global {
+            value: debug.global(&name),
+        },
+        VnDebugRequest::SetGlobal { name, value } => {
+            debug.set_global(&name, value);
+            VnDebugResponse::Ok
+        }
+        VnDebugRequest::DeleteGlobal { name } => {
+            debug.delete_global(&name);
+            VnDebugResponse::Ok
+        }
+    }
+}
+
+/// Line-based JSON protocol over any stream - each line is one request or response.
+/// Works with non-blocking streams, so it can be polled every frame.
+pub struct VnDebugConnection<T: Read + Write> {
+    stream: T,
+    buffer: Vec<u8>,
+    /// Responses not yet accepted by stream.
+    outgoing: Vec<u8>,
+}
+
+impl<T: Read + Write> VnDebugConnection<T> {
+    pub fn new(stream: T) -> Self {
+        Self {
+            stream,
+            buffer: vec![],
+            outgoing: vec![],
+        }
+    }
+
+    pub fn stream(&self) -> &T {
+        &self.stream
+    }
+
+    /// Handles all pending requests. Returns `false` when stream got closed.
+    pub fn process(&mut self, debug: &mut impl VnDebug) -> std::io::Result<bool> {
+        let mut connected = true;
+        let mut chunk = [0; 1024];
+        loop {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => {
+                    connected = false;
+                    break;
+                }
+                Ok(size) => {
+                    self.buffer.extend_from_slice(&chunk[..size]);
+                    if self.buffer.contains(&b'\n') {
+                        break;
+                    }
+                }
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) if error.kind() == ErrorKind::Interrupted => {}
+                Err(error) => return Err(error),
+            }
+        }
+        while let Some(index) = self.buffer.iter().position(|byte| *byte == b'\n') {
+            let line = self.buffer.drain(..=index).collect::<Vec<_>>();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let response = match serde_json::from_str::<VnDebugRequest>(line) {
+                Ok(request) => handle_request(debug, request),
+                Err(error) => VnDebugResponse::Error {
+                    message: error.to_string(),
+                },
+            };
+            let response = serde_json::to_string(&response)
+                .map_err(|error| std::io::Error::new(ErrorKind::InvalidData, error))?;
+            self.outgoing.extend_from_slice(response.as_bytes());
+            self.outgoing.push(b'\n');
+        }
+        self.send()?;
+        Ok(connected)
+    }
+
+    /// Writes as much of outgoing responses as stream accepts without blocking.
+    fn send(&mut self) -> std::io::Result<()> {
+        while !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                Ok(size) => {
+                    self.outgoing.drain(..size);
+                }
+                Err(error) if error.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(error) if error.kind() == ErrorKind::Interrupted => {}
+                Err(error) => return Err(error),
+            }
+        }
+        match self.stream.flush() {
+            Err(error) if error.kind() == ErrorKind::WouldBlock => Ok(()),
+            result => result,
+        }
+    }
+}
+
+/// Standard input and output as single debug stream.
+/// Input gets read on background thread, so reading never blocks.
+#[derive(Debug)]
+pub struct VnStdio {
+    lines: Receiver<Vec<u8>>,
+    pending: Vec<u8>,
+}
+
+impl Default for VnStdio {
+    fn default() -> Self {
+        let (sender, lines) = channel();
+        std::thread::spawn(move || {
+            let mut stdin = std::io::stdin().lock();
+            loop {
+                let mut line = vec![];
+                match stdin.read_until(b'\n', &mut line) {
+                    Ok(0) | Err(_) => break,
+                    Ok(_) => {
+                        if sender.send(line).is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+        Self {
+            lines,
+            pending: vec![],
+        }
+    }
+}
+
+impl Read for VnStdio {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.pending.is_empty() {
+            match self.lines.try_recv() {
+                Ok(line) => self.pending = line,
+                Err(TryRecvError::Empty) => return Err(ErrorKind::WouldBlock.into()),
+                Err(TryRecvError::Disconnected) => return Ok(0),
+            }
+        }
+        let size = buf.len().min(self.pending.len());
+        buf[..size].copy_from_slice(&self.pending[..size]);
+        self.pending.drain(..size);
+        Ok(size)
+    }
+}
+
+impl Write for VnStdio {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        std::io::stdout().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        std::io::stdout().flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_protocol() {
+        let request = serde_json::from_str::<VnDebugRequest>(
+            r#"{"command":"add_breakpoint","breakpoint":{"kind":"label","chapter":"welcome","label":"happy"}}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            request,
+            VnDebugRequest::AddBreakpoint {
+                breakpoint: VnBreakpoint::Label { .. }
+            }
+        ));
+        let response = serde_json::to_string(&VnDebugResponse::Status { paused: true }).unwrap();
+        assert_eq!(response, r#"{"response":"status","paused":true}"#);
+    }
+}
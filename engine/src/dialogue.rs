@@ -0,0 +1,148 @@
+use crate::script::{VnAction, VnChapterItem, VnStory};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VnDialogueLine {
+    pub id: String,
+    pub chapter: String,
+    /// Closest label above the line.
+    pub label: Option<String>,
+    pub who: Option<String>,
+    pub what: String,
+    pub choices: Vec<String>,
+    pub source: Option<String>,
+    pub line: Option<usize>,
+}
+
+/// Every spoken line of a story, for translators and voice recording.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VnDialogueScript {
+    pub lines: Vec<VnDialogueLine>,
+}
+
+impl VnDialogueScript {
+    pub fn new(story: &VnStory) -> Self {
+        let mut lines = vec![];
+        let chapters = story.chapters.iter().collect::<BTreeMap<_, _>>();
+        for (name, chapter) in chapters {
+            let mut label = None;
+            for (position, item) in chapter.items.iter().enumerate() {
+                match item {
+                    VnChapterItem::Label(item) => label = Some(item.to_owned()),
+                    VnChapterItem::Action(action) => {
+                        let id = match action.line_id() {
+                            Some(id) => id,
+                            None => continue,
+                        };
+                        let text = |name: &str| {
+                            action
+                                .params
+                                .get(name)
+                                .and_then(|value| value.as_text())
+                                .map(|value| value.to_owned())
+                        };
+                        lines.push(VnDialogueLine {
+                            id: id.to_owned(),
+                            chapter: name.to_owned(),
+                            label: label.to_owned(),
+                            who: text(VnAction::WHO_PARAM),
+                            what: text(VnAction::WHAT_PARAM).unwrap_or_default(),
+                            choices: action
+                                .params
+                                .get(VnAction::CHOICES_PARAM)
+                                .and_then(|choices| choices.as_array())
+                                .map(|choices| {
+                                    choices
+                                        .iter()
+                                        .filter_map(|choice| choice.as_text())
+                                        .map(|choice| choice.to_owned())
+                                        .collect()
+                                })
+                                .unwrap_or_default(),
+                            source: chapter.source.to_owned(),
+                            line: chapter.line(position),
+                        });
+                    }
+                }
+            }
+        }
+        Self { lines }
+    }
+
+    pub fn find(&self, id: &str) -> Option<&VnDialogueLine> {
+        self.lines.iter().find(|line| line.id == id)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&self.lines).expect("Could not serialize dialogue script!")
+    }
+
+    /// Choices are joined with ` | ` into single column.
+    pub fn to_csv(&self) -> String {
+        let mut result = String::from("id,chapter,label,who,what,choices,source,line\n");
+        for line in &self.lines {
+            let columns = [
+                line.id.to_owned(),
+                line.chapter.to_owned(),
+                line.label.to_owned().unwrap_or_default(),
+                line.who.to_owned().unwrap_or_default(),
+                line.what.to_owned(),
+                line.choices.join(" | "),
+                line.source.to_owned().unwrap_or_default(),
+                line.line.map(|line| line.to_string()).unwrap_or_default(),
+            ];
+            let columns = columns
+                .iter()
+                .map(|column| csv_escape(column))
+                .collect::<Vec<_>>();
+            result.push_str(&columns.join(","));
+            result.push('\n');
+        }
+        result
+    }
+}
+
+fn csv_escape(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::script::VnFile;
+
+    #[test]
+    fn test_dialogue_script() {
+        let story = VnFile::parse(
+            r#"
+            chapter main {
+                say who: rin what: "Hi, \"you\"!"
+            $ask:
+                say what: "Well?" choices: ["Yes" "No"] id: custom
+            }
+            "#,
+        )
+        .unwrap()
+        .story;
+        let script = VnDialogueScript::new(&story);
+        assert_eq!(script.lines.len(), 2);
+        assert_eq!(
+            script.find("main.a14395fb").unwrap().who.as_deref(),
+            Some("rin")
+        );
+        let line = script.find("custom").unwrap();
+        assert_eq!(line.label.as_deref(), Some("ask"));
+        assert_eq!(line.choices, vec!["Yes", "No"]);
+        assert_eq!(
+            script.to_csv(),
+            "id,chapter,label,who,what,choices,source,line\n\
+            main.a14395fb,main,,rin,\"Hi, \"\"you\"\"!\",,,3\n\
+            custom,main,ask,,Well?,Yes | No,,5\n"
+        );
+    }
+}
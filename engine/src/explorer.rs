@@ -0,0 +1,266 @@
+use crate::{
+    harness::{VnHarness, VnHarnessError, VnTranscriptEntry},
+    trace::{VnTraceRecord, VnTracer},
+    vm::{VnSnapshot, VN_MAIN_THREAD},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    cell::RefCell,
+    collections::BTreeSet,
+    fmt::{Display, Formatter},
+    rc::Rc,
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum VnRouteOutcome {
+    /// Story finished and given chapter was the last one executed.
+    Ending {
+        chapter: Option<String>,
+    },
+    /// Story could not continue.
+    DeadEnd {
+        reason: String,
+    },
+    InfiniteLoop,
+    /// Route asked for more choices than allowed.
+    DepthLimit,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VnRoute {
+    /// Choices to take in order to reach outcome.
+    pub choices: Vec<usize>,
+    pub outcome: VnRouteOutcome,
+    pub transcript: Vec<VnTranscriptEntry>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VnExplorationReport {
+    pub routes: Vec<VnRoute>,
+    /// Exploration stopped before all routes got visited.
+    pub truncated: bool,
+}
+
+impl VnExplorationReport {
+    pub fn endings(&self) -> BTreeSet<&str> {
+        self.routes
+            .iter()
+            .filter_map(|route| match &route.outcome {
+                VnRouteOutcome::Ending { chapter } => chapter.as_deref(),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Shortest choice sequence that reaches given ending chapter.
+    pub fn route_to(&self, chapter: &str) -> Option<&VnRoute> {
+        self.routes
+            .iter()
+            .filter(|route| {
+                matches!(
+                    &route.outcome,
+                    VnRouteOutcome::Ending { chapter: Some(name) } if name == chapter
+                )
+            })
+            .min_by_key(|route| route.choices.len())
+    }
+}
+
+impl Display for VnExplorationReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Explored {} routes reaching {} endings{}",
+            self.routes.len(),
+            self.endings().len(),
+            if self.truncated { " (truncated)" } else { "" }
+        )?;
+        for route in &self.routes {
+            let choices = route
+                .choices
+                .iter()
+                .map(|choice| choice.to_string())
+                .collect::<Vec<_>>()
+                .join(" ");
+            match &route.outcome {
+                VnRouteOutcome::Ending { chapter } => writeln!(
+                    f,
+                    "[{}] ending: {}",
+                    choices,
+                    chapter.as_deref().unwrap_or("?")
+                )?,
+                VnRouteOutcome::DeadEnd { reason } => {
+                    writeln!(f, "[{}] dead end: {}", choices, reason)?
+                }
+                VnRouteOutcome::InfiniteLoop => writeln!(f, "[{}] infinite loop", choices)?,
+                VnRouteOutcome::DepthLimit => writeln!(f, "[{}] depth limit", choices)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct LastChapter(Option<String>);
+
+impl VnTracer for LastChapter {
+    fn trace(&mut self, record: &VnTraceRecord) {
+        if record.thread == VN_MAIN_THREAD {
+            self.0 = Some(record.chapter.to_owned());
+        }
+    }
+}
+
+struct Branch {
+    snapshot: VnSnapshot,
+    transcript: Vec<VnTranscriptEntry>,
+    choices: Vec<usize>,
+}
+
+/// Explores all choice combinations of a story by branching VM state at every choice point.
+pub struct VnExplorer {
+    max_depth: usize,
+    max_routes: usize,
+}
+
+impl Default for VnExplorer {
+    fn default() -> Self {
+        Self {
+            max_depth: 32,
+            max_routes: 10_000,
+        }
+    }
+}
+
+impl VnExplorer {
+    /// Maximum number of choices in single route.
+    pub fn max_depth(mut self, value: usize) -> Self {
+        self.max_depth = value;
+        self
+    }
+
+    pub fn max_routes(mut self, value: usize) -> Self {
+        self.max_routes = value;
+        self
+    }
+
+    pub fn explore(
+        &self,
+        mut harness: VnHarness,
+        chapter: &str,
+    ) -> Result<VnExplorationReport, VnHarnessError> {
+        let last_chapter = Rc::new(RefCell::new(LastChapter::default()));
+        harness.vm().set_tracer(last_chapter.clone());
+        if !harness.vm().enter(chapter, None) {
+            return Err(VnHarnessError::UnknownChapter(chapter.to_owned()));
+        }
+        let mut result = VnExplorationReport::default();
+        let mut pending = vec![Branch {
+            snapshot: harness.vm().snapshot(),
+            transcript: vec![],
+            choices: vec![],
+        }];
+        while let Some(branch) = pending.pop() {
+            if result.routes.len() >= self.max_routes {
+                result.truncated = true;
+                break;
+            }
+            harness.vm().restore(branch.snapshot);
+            let transcript = harness.transcript();
+            transcript.entries = branch.transcript;
+            transcript.choices = branch.choices.last().copied().into_iter().collect();
+            transcript.error = None;
+            let outcome = match harness.resume() {
+                Ok(_) => VnRouteOutcome::Ending {
+                    chapter: last_chapter.borrow().0.to_owned(),
+                },
+                Err(VnHarnessError::MissingChoice { choices, .. }) => {
+                    if branch.choices.len() >= self.max_depth {
+                        VnRouteOutcome::DepthLimit
+                    } else {
+                        let snapshot = harness.vm().snapshot();
+                        let transcript = &harness.transcript().entries;
+                        for index in (0..choices.len()).rev() {
+                            let mut choices = branch.choices.to_owned();
+                            choices.push(index);
+                            pending.push(Branch {
+                                snapshot: snapshot.clone(),
+                                transcript: transcript.to_owned(),
+                                choices,
+                            });
+                        }
+                        continue;
+                    }
+                }
+                Err(VnHarnessError::InfiniteLoop) => VnRouteOutcome::InfiniteLoop,
+                Err(error) => VnRouteOutcome::DeadEnd {
+                    reason: error.to_string(),
+                },
+            };
+            result.routes.push(VnRoute {
+                choices: branch.choices,
+                outcome,
+                transcript: harness.transcript().entries.to_owned(),
+            });
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::script::VnFile;
+
+    #[test]
+    fn test_explorer() {
+        let story = VnFile::parse(
+            r#"
+            chapter main {
+                say what: "Where to?" choices: ["Left" "Right" "Back"]
+                jump chapter: left global: CHOICE equals: 0
+                jump chapter: right global: CHOICE equals: 1
+                jump label: stuck
+            $stuck:
+                jump label: stuck
+            }
+
+            chapter left {
+                say what: "Again?" choices: ["Yes" "No"]
+                jump chapter: main global: CHOICE equals: 0
+                exit
+            }
+
+            chapter right {
+                wait seconds: 1 skippable: false
+                exit
+            }
+            "#,
+        )
+        .unwrap()
+        .story;
+
+        let report = VnExplorer::default()
+            .max_depth(3)
+            .explore(VnHarness::new(&story), "main")
+            .unwrap();
+        assert_eq!(
+            report.endings().into_iter().collect::<Vec<_>>(),
+            vec!["left", "right"]
+        );
+        assert_eq!(report.route_to("left").unwrap().choices, vec![0, 1]);
+        assert_eq!(report.route_to("right").unwrap().choices, vec![1]);
+        assert!(
+            report
+                .routes
+                .iter()
+                .any(|route| route.choices == vec![2]
+                    && route.outcome == VnRouteOutcome::InfiniteLoop)
+        );
+        assert!(report
+            .routes
+            .iter()
+            .any(|route| route.outcome == VnRouteOutcome::DepthLimit));
+    }
+}
@@ -0,0 +1,464 @@
+use crate::{
+    localization::{VnLocalization, VN_LOCALIZATION},
+    markup::VnMarkup,
+    script::VnValue,
+    vm::{Globals, VN_GLOBALS},
+};
+use intuicio_essentials::prelude::*;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    /// Number of closest plural placeholder.
+    Hash,
+    Argument {
+        name: String,
+        raw: String,
+    },
+    Plural {
+        name: String,
+        cases: Vec<(String, Vec<Part>)>,
+        raw: String,
+    },
+    Select {
+        name: String,
+        cases: Vec<(String, Vec<Part>)>,
+        raw: String,
+    },
+}
+
+/// Formats ICU-like message pattern:
+/// - `{name}` - argument value,
+/// - `{name, plural, =0 {none} one {# item} other {# items}}` - plural category of number,
+/// - `{name, select, female {she} male {he} other {they}}` - case by value,
+/// - `{{` and `}}` - literal braces.
+///
+/// Placeholders of unknown arguments and malformed ones are left verbatim.
+pub fn format_message(
+    pattern: &str,
+    arguments: &HashMap<String, VnValue>,
+    language: Option<&str>,
+) -> String {
+    format(pattern, arguments, language, false)
+}
+
+/// Formats [`VnMarkup`] text like [`format_message`], except that markup tags and
+/// escaped braces are left for markup parser, and braces of argument values get
+/// escaped, so values show up as they are instead of being read as markup.
+pub fn format_markup(
+    pattern: &str,
+    arguments: &HashMap<String, VnValue>,
+    language: Option<&str>,
+) -> String {
+    format(pattern, arguments, language, true)
+}
+
+/// Formats message with story globals as arguments, in currently selected language.
+pub fn format_with_globals(context: &Context, pattern: &str) -> String {
+    let (arguments, language) = globals_arguments(context);
+    format_message(pattern, arguments, language)
+}
+
+/// Formats [`VnMarkup`] text with story globals as arguments, in currently selected language.
+pub fn format_markup_with_globals(context: &Context, pattern: &str) -> String {
+    let (arguments, language) = globals_arguments(context);
+    format_markup(pattern, arguments, language)
+}
+
+fn globals_arguments(context: &Context) -> (&HashMap<String, VnValue>, Option<&str>) {
+    let globals = context
+        .custom::<Globals>(VN_GLOBALS)
+        .expect("Cannot access VN globals!");
+    let language = context
+        .custom::<VnLocalization>(VN_LOCALIZATION)
+        .and_then(|localization| localization.language());
+    (&globals.properties, language)
+}
+
+fn format(
+    pattern: &str,
+    arguments: &HashMap<String, VnValue>,
+    language: Option<&str>,
+    markup: bool,
+) -> String {
+    let chars = pattern.chars().collect::<Vec<_>>();
+    let mut position = 0;
+    let parts = parse_parts(&chars, &mut position, false, false, markup);
+    let mut result = String::new();
+    write_parts(&parts, arguments, language, None, markup, &mut result);
+    result
+}
+
+/// Simplified CLDR plural category of a number in given language:
+/// `zero`, `one`, `two`, `few`, `many` or `other`.
+pub fn plural_category(language: Option<&str>, value: f64) -> &'static str {
+    let language = language
+        .and_then(|language| language.split(['-', '_']).next())
+        .unwrap_or("en");
+    if value.fract() != 0.0 {
+        return match language {
+            "fr" | "pt" if value.abs() < 2.0 => "one",
+            _ => "other",
+        };
+    }
+    let n = value.abs() as u64;
+    let (n10, n100) = (n % 10, n % 100);
+    match language {
+        "ja" | "zh" | "ko" | "th" | "vi" | "id" | "ms" => "other",
+        "fr" | "pt" => {
+            if n < 2 {
+                "one"
+            } else {
+                "other"
+            }
+        }
+        "pl" => {
+            if n == 1 {
+                "one"
+            } else if (2..=4).contains(&n10) && !(12..=14).contains(&n100) {
+                "few"
+            } else {
+                "many"
+            }
+        }
+        "ru" | "uk" | "be" => {
+            if n10 == 1 && n100 != 11 {
+                "one"
+            } else if (2..=4).contains(&n10) && !(12..=14).contains(&n100) {
+                "few"
+            } else {
+                "many"
+            }
+        }
+        "cs" | "sk" => match n {
+            1 => "one",
+            2..=4 => "few",
+            _ => "other",
+        },
+        "ar" => match (n, n100) {
+            (0, _) => "zero",
+            (1, _) => "one",
+            (2, _) => "two",
+            (_, 3..=10) => "few",
+            (_, 11..=99) => "many",
+            _ => "other",
+        },
+        _ => {
+            if n == 1 {
+                "one"
+            } else {
+                "other"
+            }
+        }
+    }
+}
+
+pub fn value_to_text(value: &VnValue) -> String {
+    match value {
+        VnValue::None => String::new(),
+        VnValue::Boolean(value) => value.to_string(),
+        VnValue::Integer(value) => value.to_string(),
+        VnValue::Number(value) => value.to_string(),
+        VnValue::Text(value) => value.to_owned(),
+        VnValue::Color(value) => format!("#{:08x}", value),
+        VnValue::Vec2 { x, y } => format!("{}, {}", x, y),
+        VnValue::Rect {
+            x,
+            y,
+            width,
+            height,
+        } => format!("{}, {}, {}, {}", x, y, width, height),
+        VnValue::Reference { name, .. } => name.to_owned(),
+        VnValue::Array(items) => items
+            .iter()
+            .map(value_to_text)
+            .collect::<Vec<_>>()
+            .join(", "),
+        VnValue::Map(_) => format!("{:?}", value),
+    }
+}
+
+fn parse_parts(
+    chars: &[char],
+    position: &mut usize,
+    nested: bool,
+    in_plural: bool,
+    markup: bool,
+) -> Vec<Part> {
+    let mut result = vec![];
+    let mut text = String::new();
+    while let Some(c) = chars.get(*position).copied() {
+        match c {
+            '{' if chars.get(*position + 1) == Some(&'{') => {
+                text.push_str(if markup { "{{" } else { "{" });
+                *position += 2;
+            }
+            '}' if nested => break,
+            '}' if chars.get(*position + 1) == Some(&'}') => {
+                text.push_str(if markup { "}}" } else { "}" });
+                *position += 2;
+            }
+            '#' if in_plural => {
+                if !text.is_empty() {
+                    result.push(Part::Text(std::mem::take(&mut text)));
+                }
+                result.push(Part::Hash);
+                *position += 1;
+            }
+            '{' => {
+                if let Some(length) = markup
+                    .then(|| markup_tag_length(&chars[*position..]))
+                    .flatten()
+                {
+                    text.extend(&chars[*position..*position + length]);
+                    *position += length;
+                    continue;
+                }
+                let start = *position;
+                match parse_placeholder(chars, position, in_plural, markup) {
+                    Some(part) => {
+                        if !text.is_empty() {
+                            result.push(Part::Text(std::mem::take(&mut text)));
+                        }
+                        result.push(part);
+                    }
+                    None => {
+                        text.push('{');
+                        *position = start + 1;
+                    }
+                }
+            }
+            c => {
+                text.push(c);
+                *position += 1;
+            }
+        }
+    }
+    if !text.is_empty() {
+        result.push(Part::Text(text));
+    }
+    result
+}
+
+fn parse_placeholder(
+    chars: &[char],
+    position: &mut usize,
+    in_plural: bool,
+    markup: bool,
+) -> Option<Part> {
+    let start = *position;
+    *position += 1;
+    let name = parse_token(chars, position);
+    if name.is_empty() {
+        return None;
+    }
+    skip_whitespace(chars, position);
+    match chars.get(*position)? {
+        '}' => {
+            *position += 1;
+            return Some(Part::Argument {
+                name,
+                raw: chars[start..*position].iter().collect(),
+            });
+        }
+        ',' => *position += 1,
+        _ => return None,
+    }
+    skip_whitespace(chars, position);
+    let kind = parse_token(chars, position);
+    let plural = match kind.as_str() {
+        "plural" => true,
+        "select" => false,
+        _ => return None,
+    };
+    skip_whitespace(chars, position);
+    if chars.get(*position)? != &',' {
+        return None;
+    }
+    *position += 1;
+    let mut cases = vec![];
+    loop {
+        skip_whitespace(chars, position);
+        match chars.get(*position)? {
+            '}' => {
+                *position += 1;
+                break;
+            }
+            '{' => return None,
+            _ => {}
+        }
+        let selector = parse_token(chars, position);
+        skip_whitespace(chars, position);
+        if selector.is_empty() || chars.get(*position)? != &'{' {
+            return None;
+        }
+        *position += 1;
+        let parts = parse_parts(chars, position, true, plural || in_plural, markup);
+        if chars.get(*position)? != &'}' {
+            return None;
+        }
+        *position += 1;
+        cases.push((selector, parts));
+    }
+    let raw = chars[start..*position].iter().collect();
+    Some(if plural {
+        Part::Plural { name, cases, raw }
+    } else {
+        Part::Select { name, cases, raw }
+    })
+}
+
+fn parse_token(chars: &[char], position: &mut usize) -> String {
+    let mut result = String::new();
+    while let Some(c) = chars.get(*position) {
+        if c.is_alphanumeric() || *c == '_' || *c == '=' || *c == '.' || *c == '-' {
+            result.push(*c);
+            *position += 1;
+        } else {
+            break;
+        }
+    }
+    result
+}
+
+fn skip_whitespace(chars: &[char], position: &mut usize) {
+    while chars
+        .get(*position)
+        .map(|c| c.is_whitespace())
+        .unwrap_or_default()
+    {
+        *position += 1;
+    }
+}
+
+fn find_case<'a>(cases: &'a [(String, Vec<Part>)], selectors: &[&str]) -> Option<&'a [Part]> {
+    selectors.iter().find_map(|selector| {
+        cases
+            .iter()
+            .find(|(name, _)| name == selector)
+            .map(|(_, parts)| parts.as_slice())
+    })
+}
+
+/// Length of `{tag}` at the beginning of chars, if it is markup tag.
+fn markup_tag_length(chars: &[char]) -> Option<usize> {
+    let end = chars.iter().skip(1).position(|c| *c == '{' || *c == '}')? + 1;
+    if chars[end] != '}' {
+        return None;
+    }
+    let tag = chars[1..end].iter().collect::<String>();
+    VnMarkup::is_tag(&tag).then_some(end + 1)
+}
+
+fn write_parts(
+    parts: &[Part],
+    arguments: &HashMap<String, VnValue>,
+    language: Option<&str>,
+    number: Option<f64>,
+    markup: bool,
+    result: &mut String,
+) {
+    for part in parts {
+        match part {
+            Part::Text(text) => result.push_str(text),
+            Part::Hash => match number {
+                Some(number) => result.push_str(&number.to_string()),
+                None => result.push('#'),
+            },
+            Part::Argument { name, raw } => match arguments.get(name) {
+                Some(value) if markup => {
+                    result.push_str(&value_to_text(value).replace('{', "{{").replace('}', "}}"))
+                }
+                Some(value) => result.push_str(&value_to_text(value)),
+                None => result.push_str(raw),
+            },
+            Part::Plural { name, cases, raw } => {
+                let value = arguments.get(name).and_then(|value| value.as_number());
+                let case = value.and_then(|value| {
+                    let exact = format!("={}", value);
+                    find_case(
+                        cases,
+                        &[exact.as_str(), plural_category(language, value), "other"],
+                    )
+                });
+                match case {
+                    Some(case) => write_parts(case, arguments, language, value, markup, result),
+                    None => result.push_str(raw),
+                }
+            }
+            Part::Select { name, cases, raw } => {
+                let value = arguments.get(name).map(value_to_text);
+                let case = value
+                    .as_deref()
+                    .and_then(|value| find_case(cases, &[value, "other"]));
+                match case {
+                    Some(case) => write_parts(case, arguments, language, number, markup, result),
+                    None => result.push_str(raw),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        let arguments = [
+            ("n".to_owned(), VnValue::Number(3.0)),
+            ("who".to_owned(), VnValue::Text("Rin".to_owned())),
+            ("gender".to_owned(), VnValue::Text("female".to_owned())),
+        ]
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+        let coins = "{who} has {n, plural, =0 {no coins} one {# coin} other {# coins}}";
+        assert_eq!(format_message(coins, &arguments, None), "Rin has 3 coins");
+        assert_eq!(
+            format_message(
+                "{n, plural, one {# moneta} few {# monety} many {# monet}}",
+                &arguments,
+                Some("pl-PL")
+            ),
+            "3 monety"
+        );
+        assert_eq!(
+            format_message(
+                "{gender, select, female {She} male {He} other {They}} won",
+                &arguments,
+                None
+            ),
+            "She won"
+        );
+        assert_eq!(
+            format_message("{{literal}} {unknown} {n, broken", &arguments, None),
+            "{literal} {unknown} {n, broken"
+        );
+        assert_eq!(plural_category(Some("ru"), 21.0), "one");
+        assert_eq!(plural_category(Some("en"), 0.0), "other");
+        assert_eq!(plural_category(Some("fr"), 0.0), "one");
+    }
+
+    #[test]
+    fn test_format_markup() {
+        let arguments = [
+            ("b".to_owned(), VnValue::Text("bold".to_owned())),
+            ("who".to_owned(), VnValue::Text("{i}Rin{/i}".to_owned())),
+        ]
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+        let text = format_markup(
+            "{b}{who}{/b} says {{b}} {color=#f00}{b}{/color}",
+            &arguments,
+            None,
+        );
+        assert_eq!(
+            text,
+            "{b}{{i}}Rin{{/i}}{/b} says {{b}} {color=#f00}{b}{/color}"
+        );
+        assert_eq!(VnMarkup::parse(&text).plain_text(), "{i}Rin{/i} says {b} ");
+        assert_eq!(format_message("{b}", &arguments, None), "bold");
+    }
+}
@@ -0,0 +1,377 @@
+use crate::script::{VnAction, VnChapterItem, VnStory, VnValue};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt::Write};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VnGraphNodeKind {
+    /// Beginning of chapter.
+    Chapter,
+    Label,
+    /// Leaving chapter back to its caller.
+    Exit,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VnGraphNode {
+    pub id: String,
+    pub kind: VnGraphNodeKind,
+    pub chapter: String,
+    pub label: Option<String>,
+    /// Events that make VM enter this chapter.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub triggers: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VnGraphEdgeKind {
+    /// Execution falls through into next label.
+    Next,
+    Jump,
+    Enter,
+    Exit,
+    Random,
+    Spawn,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VnGraphEdge {
+    pub from: String,
+    pub to: String,
+    pub kind: VnGraphEdgeKind,
+    pub condition: Option<String>,
+}
+
+/// Chapter and label flow graph of a story.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VnStoryGraph {
+    pub nodes: Vec<VnGraphNode>,
+    pub edges: Vec<VnGraphEdge>,
+}
+
+impl VnStoryGraph {
+    pub fn new(story: &VnStory) -> Self {
+        let mut result = Self::default();
+        let chapters = story.chapters.iter().collect::<BTreeMap<_, _>>();
+        for (name, chapter) in chapters {
+            result.nodes.push(VnGraphNode {
+                id: name.to_owned(),
+                kind: VnGraphNodeKind::Chapter,
+                chapter: name.to_owned(),
+                label: None,
+                triggers: chapter
+                    .triggers
+                    .iter()
+                    .map(|trigger| match trigger.argument.as_ref() {
+                        Some(argument) => format!("{} {}", trigger.event, argument),
+                        None => trigger.event.to_owned(),
+                    })
+                    .collect(),
+            });
+            let exit = format!("{}:exit", name);
+            let mut has_exit = false;
+            let mut current = name.to_owned();
+            let mut reachable = true;
+            for item in &chapter.items {
+                match item {
+                    VnChapterItem::Label(label) => {
+                        let id = node_id(name, Some(label));
+                        result.nodes.push(VnGraphNode {
+                            id: id.to_owned(),
+                            kind: VnGraphNodeKind::Label,
+                            chapter: name.to_owned(),
+                            label: Some(label.to_owned()),
+                            triggers: vec![],
+                        });
+                        if reachable {
+                            result.edge(&current, &id, VnGraphEdgeKind::Next, None);
+                        }
+                        current = id;
+                        reachable = true;
+                    }
+                    VnChapterItem::Action(action) => {
+                        if !reachable || !is_core(action) {
+                            continue;
+                        }
+                        let condition = condition(action);
+                        let unconditional = condition.is_none();
+                        match action.name.as_str() {
+                            "jump" => {
+                                let to = target(name, action);
+                                result.edge(&current, &to, VnGraphEdgeKind::Jump, condition);
+                                reachable = !unconditional;
+                            }
+                            "enter" => {
+                                let to = target(name, action);
+                                result.edge(&current, &to, VnGraphEdgeKind::Enter, condition);
+                            }
+                            "exit" => {
+                                result.edge(&current, &exit, VnGraphEdgeKind::Exit, condition);
+                                has_exit = true;
+                                reachable = !unconditional;
+                            }
+                            "spawn" => {
+                                let to = target(name, action);
+                                let thread = action
+                                    .params
+                                    .get("thread")
+                                    .and_then(|thread| thread.as_text());
+                                result.edge(
+                                    &current,
+                                    &to,
+                                    VnGraphEdgeKind::Spawn,
+                                    thread.map(|thread| format!("thread {}", thread)),
+                                );
+                            }
+                            "jump_random" => {
+                                let chapter = action
+                                    .params
+                                    .get("chapter")
+                                    .and_then(|chapter| chapter.as_text())
+                                    .unwrap_or(name);
+                                let labels = action
+                                    .params
+                                    .get("labels")
+                                    .and_then(|labels| labels.as_array())
+                                    .unwrap_or_default();
+                                let weights = action
+                                    .params
+                                    .get("weights")
+                                    .and_then(|weights| weights.as_array());
+                                for (index, label) in labels.iter().enumerate() {
+                                    let condition = weights
+                                        .and_then(|weights| weights.get(index))
+                                        .map(|weight| format!("weight {}", value_text(weight)));
+                                    result.edge(
+                                        &current,
+                                        &node_id(chapter, label.as_text()),
+                                        VnGraphEdgeKind::Random,
+                                        condition,
+                                    );
+                                }
+                                reachable = labels.is_empty();
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            }
+            if has_exit {
+                result.nodes.push(VnGraphNode {
+                    id: exit,
+                    kind: VnGraphNodeKind::Exit,
+                    chapter: name.to_owned(),
+                    label: None,
+                    triggers: vec![],
+                });
+            }
+        }
+        result
+    }
+
+    fn edge(&mut self, from: &str, to: &str, kind: VnGraphEdgeKind, condition: Option<String>) {
+        self.edges.push(VnGraphEdge {
+            from: from.to_owned(),
+            to: to.to_owned(),
+            kind,
+            condition,
+        });
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Could not serialize story graph!")
+    }
+
+    /// Graphviz DOT representation, chapters being clusters of their labels.
+    pub fn to_dot(&self) -> String {
+        let mut result = String::new();
+        let _ = writeln!(result, "digraph story {{");
+        let _ = writeln!(result, "  node [shape=box];");
+        let mut clusters = BTreeMap::<&str, Vec<&VnGraphNode>>::new();
+        for node in &self.nodes {
+            clusters.entry(&node.chapter).or_default().push(node);
+        }
+        for (chapter, nodes) in clusters {
+            let _ = writeln!(result, "  subgraph {:?} {{", format!("cluster_{}", chapter));
+            let _ = writeln!(result, "    label={:?};", chapter);
+            for node in nodes {
+                let (label, shape) = match node.kind {
+                    VnGraphNodeKind::Chapter => {
+                        let mut label = node.chapter.to_owned();
+                        for trigger in &node.triggers {
+                            let _ = write!(label, "\non {}", trigger);
+                        }
+                        (label, "box")
+                    }
+                    VnGraphNodeKind::Label => (
+                        format!("${}", node.label.as_deref().unwrap_or_default()),
+                        "ellipse",
+                    ),
+                    VnGraphNodeKind::Exit => ("exit".to_owned(), "doublecircle"),
+                };
+                let _ = writeln!(
+                    result,
+                    "    {:?} [label={:?}, shape={}];",
+                    node.id, label, shape
+                );
+            }
+            let _ = writeln!(result, "  }}");
+        }
+        for edge in &self.edges {
+            let style = match edge.kind {
+                VnGraphEdgeKind::Next => "dotted",
+                VnGraphEdgeKind::Enter | VnGraphEdgeKind::Spawn => "dashed",
+                _ => "solid",
+            };
+            let _ = write!(
+                result,
+                "  {:?} -> {:?} [style={}",
+                edge.from, edge.to, style
+            );
+            if let Some(condition) = edge.condition.as_ref() {
+                let _ = write!(result, ", label={:?}", condition);
+            }
+            let _ = writeln!(result, "];");
+        }
+        let _ = writeln!(result, "}}");
+        result
+    }
+}
+
+fn node_id(chapter: &str, label: Option<&str>) -> String {
+    match label {
+        Some(label) => format!("{}${}", chapter, label),
+        None => chapter.to_owned(),
+    }
+}
+
+fn is_core(action: &VnAction) -> bool {
+    action
+        .module_name
+        .as_deref()
+        .map(|module_name| module_name == "vn")
+        .unwrap_or(true)
+}
+
+fn target(chapter: &str, action: &VnAction) -> String {
+    let chapter = action
+        .params
+        .get("chapter")
+        .and_then(|chapter| chapter.as_text())
+        .unwrap_or(chapter);
+    let label = action.params.get("label").and_then(|label| label.as_text());
+    node_id(chapter, label)
+}
+
+fn condition(action: &VnAction) -> Option<String> {
+    let global = action.params.get("global")?.as_text()?;
+    let mut result = vec![];
+    for (param, operator) in [
+        ("is_type", "is type of"),
+        ("equals", "=="),
+        ("not_equals", "!="),
+        ("less_than", "<"),
+        ("greater_than", ">"),
+        ("has_items", "has"),
+    ] {
+        if let Some(value) = action.params.get(param) {
+            result.push(format!("{} {} {}", global, operator, value_text(value)));
+        }
+    }
+    if result.is_empty() {
+        Some(format!("{} exists", global))
+    } else {
+        Some(result.join(" && "))
+    }
+}
+
+fn value_text(value: &VnValue) -> String {
+    match value {
+        VnValue::None => "none".to_owned(),
+        VnValue::Boolean(value) => value.to_string(),
+        VnValue::Integer(value) => value.to_string(),
+        VnValue::Number(value) => value.to_string(),
+        VnValue::Text(value) => format!("{:?}", value),
+        VnValue::Color(value) => format!("#{:08x}", value),
+        VnValue::Vec2 { x, y } => format!("vec2({}, {})", x, y),
+        VnValue::Rect {
+            x,
+            y,
+            width,
+            height,
+        } => format!("rect({}, {}, {}, {})", x, y, width, height),
+        VnValue::Reference { kind, name } => format!("{:?}({})", kind, name).to_lowercase(),
+        VnValue::Array(items) => format!(
+            "[{}]",
+            items.iter().map(value_text).collect::<Vec<_>>().join(" ")
+        ),
+        VnValue::Map(items) => format!(
+            "{{{}}}",
+            items
+                .iter()
+                .collect::<BTreeMap<_, _>>()
+                .into_iter()
+                .map(|(key, value)| format!("{}: {}", key, value_text(value)))
+                .collect::<Vec<_>>()
+                .join(" ")
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::script::VnFile;
+
+    #[test]
+    fn test_graph() {
+        let story = VnFile::parse(
+            r#"
+            chapter main {
+                enter chapter: intro
+                jump label: happy global: CHOICE equals: 0
+                jump chapter: sad
+            $happy:
+                exit
+            }
+
+            chapter intro {
+                exit
+            }
+
+            chapter sad {
+                exit
+            }
+            "#,
+        )
+        .unwrap()
+        .story;
+        let graph = VnStoryGraph::new(&story);
+        let edge = |from: &str, to: &str| {
+            graph
+                .edges
+                .iter()
+                .find(|edge| edge.from == from && edge.to == to)
+                .cloned()
+        };
+        assert_eq!(edge("main", "intro").unwrap().kind, VnGraphEdgeKind::Enter);
+        assert_eq!(
+            edge("main", "main$happy").unwrap().condition.as_deref(),
+            Some("CHOICE == 0")
+        );
+        assert_eq!(edge("main", "sad").unwrap().condition, None);
+        // unconditional jump makes label unreachable by falling through
+        assert!(graph
+            .edges
+            .iter()
+            .all(|edge| edge.kind != VnGraphEdgeKind::Next));
+        assert_eq!(
+            edge("main$happy", "main:exit").unwrap().kind,
+            VnGraphEdgeKind::Exit
+        );
+        assert!(graph
+            .to_dot()
+            .contains(r#""main" -> "main$happy" [style=solid, label="CHOICE == 0"];"#));
+    }
+}
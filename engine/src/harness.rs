@@ -0,0 +1,477 @@
+use crate::{
+    action::*,
+    format::{format_markup_with_globals, format_with_globals},
+    markup::{VnMarkup, VnTextSpeed},
+    script::*,
+    vm::*,
+};
+use intuicio_essentials::{core as intuicio_core, data as intuicio_data, prelude::*};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    error::Error,
+    fmt::{Display, Formatter},
+};
+
+pub const VN_TRANSCRIPT: &str = "vn-transcript";
+const DEFAULT_MAX_STEPS: usize = 100_000;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum VnTranscriptEntry {
+    Say {
+        who: Option<String>,
+        what: String,
+        choices: Vec<String>,
+    },
+    Scene {
+        name: String,
+    },
+    Show {
+        character: String,
+        variant: Option<String>,
+    },
+    Hide {
+        character: String,
+    },
+    ShowScreen {
+        name: String,
+        module_name: String,
+    },
+    HideScreen {
+        name: String,
+        module_name: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VnHarnessError {
+    UnknownChapter(String),
+    /// Story asked for choice but there was no scripted choice left.
+    MissingChoice {
+        what: String,
+        choices: Vec<String>,
+    },
+    InvalidChoice {
+        what: String,
+        choices: Vec<String>,
+        choice: usize,
+    },
+    /// Story got suspended by something that is not resolved by harness.
+    Stuck,
+    InfiniteLoop,
+    Paused,
+    StepsLimit,
+    /// Story did not pass [`VnStory::validate_actions`].
+    InvalidStory(Vec<String>),
+}
+
+impl Display for VnHarnessError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownChapter(name) => write!(f, "Unknown chapter: {}", name),
+            Self::MissingChoice { what, choices } => {
+                write!(f, "Missing choice for: {:?} with: {:?}", what, choices)
+            }
+            Self::InvalidChoice {
+                what,
+                choices,
+                choice,
+            } => write!(
+                f,
+                "Invalid choice {} for: {:?} with: {:?}",
+                choice, what, choices
+            ),
+            Self::Stuck => write!(f, "Story got stuck"),
+            Self::InfiniteLoop => write!(f, "Story got into infinite loop"),
+            Self::Paused => write!(f, "Story got paused by debugger"),
+            Self::StepsLimit => write!(f, "Story exceeded steps limit"),
+            Self::InvalidStory(errors) => write!(f, "Story is not valid: {}", errors.join(", ")),
+        }
+    }
+}
+
+impl Error for VnHarnessError {}
+
+/// Everything that stub functions have seen, plus choices to feed into `CHOICE` global.
+#[derive(Debug, Default)]
+pub struct VnTranscript {
+    pub entries: Vec<VnTranscriptEntry>,
+    pub choices: VecDeque<usize>,
+    pub error: Option<VnHarnessError>,
+    /// Pace of dialogue lines reveal, so story time passes like in runner.
+    text_speed: VnTextSpeed,
+}
+
+impl VnTranscript {
+    pub fn lines(&self) -> impl Iterator<Item = (Option<&str>, &str)> {
+        self.entries.iter().filter_map(|entry| match entry {
+            VnTranscriptEntry::Say { who, what, .. } => Some((who.as_deref(), what.as_str())),
+            _ => None,
+        })
+    }
+
+    pub fn said(&self, who: Option<&str>, what: &str) -> bool {
+        self.lines()
+            .any(|(line_who, line_what)| line_who == who && line_what == what)
+    }
+
+    pub fn scenes(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().filter_map(|entry| match entry {
+            VnTranscriptEntry::Scene { name } => Some(name.as_str()),
+            _ => None,
+        })
+    }
+}
+
+fn transcript(context: &mut Context) -> &mut VnTranscript {
+    context
+        .custom_mut::<VnTranscript>(VN_TRANSCRIPT)
+        .expect("Cannot access VN transcript!")
+}
+
+// Stubs take same params as runner functions, so stories valid for runner are valid here,
+// but presentation ones are ignored.
+
+#[allow(clippy::too_many_arguments, unused_variables)]
+#[intuicio_function(module_name = "vn_dialog", use_context)]
+pub fn say(
+    context: &mut Context,
+    who: VnValue,
+    what: VnValue,
+    choices: VnValue,
+    color: VnValue,
+    duration: VnValue,
+    ease_in: VnValue,
+    ease_out: VnValue,
+    ease_in_out: VnValue,
+    non_blocking: VnValue,
+) -> VnResult {
+    let who = who.as_text().map(|who| who.to_owned());
+    let what = format_markup_with_globals(context, what.as_text().expect("`what` is not a text!"));
+    let choices = choices
+        .as_array()
+        .map(|choices| {
+            choices
+                .iter()
+                .map(|choice| {
+                    let choice = choice.as_text().expect("`choices` item is not a text!");
+                    format_with_globals(context, choice)
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    let choice = if choices.is_empty() {
+        None
+    } else {
+        let transcript = transcript(context);
+        match transcript.choices.pop_front() {
+            Some(choice) if choice < choices.len() => Some(choice),
+            Some(choice) => {
+                transcript.error = Some(VnHarnessError::InvalidChoice {
+                    what,
+                    choices,
+                    choice,
+                });
+                return VnResult::Wait;
+            }
+            None => {
+                transcript.error = Some(VnHarnessError::MissingChoice { what, choices });
+                return VnResult::Wait;
+            }
+        }
+    };
+    let transcript = transcript(context);
+    let reveal_duration = VnMarkup::parse(&what).duration(&transcript.text_speed);
+    transcript
+        .entries
+        .push(VnTranscriptEntry::Say { who, what, choices });
+    let globals = context
+        .custom_mut::<Globals>(VN_GLOBALS)
+        .expect("Cannot access VN globals!");
+    if let Some(choice) = choice {
+        globals
+            .properties
+            .insert(VN_CHOICE_GLOBAL.to_owned(), VnValue::Integer(choice as i64));
+    }
+    // Harness reads every line till it is fully revealed, instead of skipping it like players can.
+    if reveal_duration > 0.0 {
+        VnResult::Suspend(globals.wait(reveal_duration, false))
+    } else {
+        VnResult::Continue
+    }
+}
+
+#[allow(unused_variables)]
+#[intuicio_function(module_name = "vn_scene", use_context)]
+pub fn scene(
+    context: &mut Context,
+    name: VnValue,
+    duration: VnValue,
+    ease_in: VnValue,
+    ease_out: VnValue,
+    ease_in_out: VnValue,
+) -> VnResult {
+    let name = name.as_text().expect("`name` is not a text!").to_owned();
+    transcript(context)
+        .entries
+        .push(VnTranscriptEntry::Scene { name });
+    VnResult::Continue
+}
+
+#[allow(clippy::too_many_arguments, unused_variables)]
+#[intuicio_function(module_name = "vn_character", use_context)]
+pub fn show(
+    context: &mut Context,
+    character: VnValue,
+    variant: VnValue,
+    tint: VnValue,
+    duration: VnValue,
+    ease_in: VnValue,
+    ease_out: VnValue,
+    ease_in_out: VnValue,
+) -> VnResult {
+    let character = character
+        .as_text()
+        .expect("`character` is not a text!")
+        .to_owned();
+    let variant = variant.as_text().map(|variant| variant.to_owned());
+    transcript(context)
+        .entries
+        .push(VnTranscriptEntry::Show { character, variant });
+    VnResult::Continue
+}
+
+#[allow(unused_variables)]
+#[intuicio_function(module_name = "vn_character", use_context)]
+pub fn hide(
+    context: &mut Context,
+    character: VnValue,
+    duration: VnValue,
+    ease_in: VnValue,
+    ease_out: VnValue,
+    ease_in_out: VnValue,
+) -> VnResult {
+    let character = character
+        .as_text()
+        .expect("`character` is not a text!")
+        .to_owned();
+    transcript(context)
+        .entries
+        .push(VnTranscriptEntry::Hide { character });
+    VnResult::Continue
+}
+
+#[intuicio_function(module_name = "vn_screen", use_context)]
+pub fn show_screen(context: &mut Context, name: VnValue, module_name: VnValue) -> VnResult {
+    let name = name.as_text().expect("`name` is not a text!").to_owned();
+    let module_name = module_name
+        .as_text()
+        .expect("`module_name` is not a text!")
+        .to_owned();
+    transcript(context)
+        .entries
+        .push(VnTranscriptEntry::ShowScreen { name, module_name });
+    VnResult::Continue
+}
+
+#[intuicio_function(module_name = "vn_screen", use_context)]
+pub fn hide_screen(context: &mut Context, name: VnValue, module_name: VnValue) -> VnResult {
+    let name = name.as_text().expect("`name` is not a text!").to_owned();
+    let module_name = module_name
+        .as_text()
+        .expect("`module_name` is not a text!")
+        .to_owned();
+    transcript(context)
+        .entries
+        .push(VnTranscriptEntry::HideScreen { name, module_name });
+    VnResult::Continue
+}
+
+/// Installs stubs of runner `vn_dialog`, `vn_scene`, `vn_character` and `vn_screen` functions.
+pub fn install(registry: &mut Registry) {
+    registry.add_function(
+        VnActionDefinition::new("say")
+            .module_name("vn_dialog")
+            .param(VnActionParam::new(VnAction::WHAT_PARAM).required())
+            .annotate(say::define_function(registry)),
+    );
+    registry.add_function(scene::define_function(registry));
+    registry.add_function(show::define_function(registry));
+    registry.add_function(hide::define_function(registry));
+    registry.add_function(show_screen::define_function(registry));
+    registry.add_function(hide_screen::define_function(registry));
+}
+
+/// Runs story headlessly with scripted choices and records transcript.
+pub struct VnHarness {
+    vm: Vm,
+    max_steps: usize,
+    errors: Option<Vec<String>>,
+}
+
+impl VnHarness {
+    pub fn new(story: &VnStory) -> Self {
+        let mut registry = Registry::default().with_basic_types();
+        crate::library::install(&mut registry);
+        install(&mut registry);
+        Self::with_registry(story, registry)
+    }
+
+    /// Registry has to contain stub functions (see [`install`]).
+    pub fn with_registry(story: &VnStory, registry: Registry) -> Self {
+        let host = Host::new(Context::new(1024, 1024, 1024), registry.into());
+        let mut vm = Vm::new(host);
+        let mut errors = vm.add_story(story).err();
+        let text_speed = VnTextSpeed::from_story(story).unwrap_or_else(|error| {
+            errors.get_or_insert_with(Vec::new).push(format!(
+                "config {}: {}",
+                VnStory::DIALOGUE_CONFIG,
+                error
+            ));
+            Default::default()
+        });
+        vm.host_mut().context().set_custom(
+            VN_TRANSCRIPT,
+            VnTranscript {
+                text_speed,
+                ..Default::default()
+            },
+        );
+        Self {
+            vm,
+            max_steps: DEFAULT_MAX_STEPS,
+            errors,
+        }
+    }
+
+    pub fn max_steps(mut self, value: usize) -> Self {
+        self.max_steps = value;
+        self
+    }
+
+    pub fn choices(mut self, choices: impl IntoIterator<Item = usize>) -> Self {
+        self.push_choices(choices);
+        self
+    }
+
+    pub fn push_choices(&mut self, choices: impl IntoIterator<Item = usize>) {
+        self.transcript().choices.extend(choices);
+    }
+
+    pub fn vm(&mut self) -> &mut Vm {
+        &mut self.vm
+    }
+
+    pub fn transcript(&mut self) -> &mut VnTranscript {
+        transcript(self.vm.host_mut().context())
+    }
+
+    /// Enters chapter and runs story until it finishes.
+    pub fn run(&mut self, chapter: &str) -> Result<&VnTranscript, VnHarnessError> {
+        if let Some(errors) = self.errors.as_ref() {
+            return Err(VnHarnessError::InvalidStory(errors.to_owned()));
+        }
+        if !self.vm.enter(chapter, None) {
+            return Err(VnHarnessError::UnknownChapter(chapter.to_owned()));
+        }
+        self.resume()
+    }
+
+    /// Continues already running story until it finishes.
+    pub fn resume(&mut self) -> Result<&VnTranscript, VnHarnessError> {
+        let mut steps_left = self.max_steps;
+        while steps_left > 0 {
+            let result = self.vm.run(steps_left.min(1024));
+            steps_left = steps_left.saturating_sub(1024);
+            if let Some(error) = self.transcript().error.take() {
+                return Err(error);
+            }
+            match result {
+                VnRunResult::Finished => return Ok(self.transcript()),
+                VnRunResult::InfiniteLoop => return Err(VnHarnessError::InfiniteLoop),
+                VnRunResult::Paused => return Err(VnHarnessError::Paused),
+                VnRunResult::BudgetExhausted => {}
+                VnRunResult::Suspended => {
+                    // Only timers get resolved here, nothing else can resume the story.
+                    if self.vm.globals().next_timer().is_none() {
+                        return Err(VnHarnessError::Stuck);
+                    }
+                    self.vm.skip_waits();
+                    let delta_time = self.vm.globals().next_timer().unwrap_or_default();
+                    self.vm.advance_time(delta_time);
+                }
+            }
+        }
+        Err(VnHarnessError::StepsLimit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_harness() {
+        let story = VnFile::parse(
+            r#"
+            chapter main {
+                scene name: street
+                say who: rin what: "Hi!" choices: ["Hello" "Bye"]
+                jump label: bye global: CHOICE equals: 1
+                say who: rin what: "Nice to meet you!"
+                exit
+            $bye:
+                wait seconds: 10
+                say what: "Goodbye!"
+                exit
+            }
+            "#,
+        )
+        .unwrap()
+        .story;
+
+        let mut harness = VnHarness::new(&story).choices([0]);
+        let transcript = harness.run("main").unwrap();
+        assert_eq!(transcript.scenes().collect::<Vec<_>>(), vec!["street"]);
+        assert!(transcript.said(Some("rin"), "Nice to meet you!"));
+        assert!(!transcript.said(None, "Goodbye!"));
+
+        let mut harness = VnHarness::new(&story).choices([1]);
+        let transcript = harness.run("main").unwrap();
+        assert!(transcript.said(None, "Goodbye!"));
+
+        let mut harness = VnHarness::new(&story);
+        assert!(matches!(
+            harness.run("main"),
+            Err(VnHarnessError::MissingChoice { .. })
+        ));
+    }
+
+    #[test]
+    fn test_harness_time() {
+        let story = VnFile::parse(
+            r#"
+            config dialogue {
+                text_speed: 10
+                sentence_pause: 0
+            }
+
+            chapter main {
+                set_global name: tag value: "{b}"
+                say what: "Hello {tag}"
+                wait seconds: 1 skippable: false
+                wait seconds: 5
+                exit
+            }
+            "#,
+        )
+        .unwrap()
+        .story;
+
+        let mut harness = VnHarness::new(&story);
+        let transcript = harness.run("main").unwrap();
+        // Globals are escaped like in runner, so they do not turn into markup.
+        assert!(transcript.said(None, "Hello {{b}}"));
+        assert_eq!(harness.vm().globals().time, 1.9);
+    }
+}
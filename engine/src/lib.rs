@@ -1,8 +1,23 @@
+pub mod action;
+pub mod convert;
+pub mod debugger;
+pub mod dialogue;
+pub mod explorer;
+pub mod format;
+pub mod graph;
+pub mod harness;
 pub mod library;
+pub mod localization;
+pub mod markup;
 pub mod parser;
+pub mod random;
 pub mod script;
+pub mod trace;
 pub mod vm;
 
 pub mod prelude {
-    pub use crate::{script::*, vm::*};
+    pub use crate::{
+        action::*, convert::*, debugger::*, dialogue::*, explorer::*, format::*, graph::*,
+        harness::*, localization::*, markup::*, random::*, script::*, trace::*, vm::*,
+    };
 }
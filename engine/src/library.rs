@@ -1,23 +1,79 @@
-use crate::{script::*, vm::*};
+use crate::{action::*, localization::*, script::*, vm::*};
 use intuicio_essentials::{core as intuicio_core, data as intuicio_data, prelude::*};
 
-#[intuicio_function(module_name = "vn", use_context)]
-pub fn set_global(context: &mut Context, name: VnValue, value: VnValue) -> VnResult {
-    let name = name.as_text().expect("`name` is not a text!");
+pub fn set_global(context: &mut Context, name: String, value: VnValue) -> VnResult {
     let globals = context
         .custom_mut::<Globals>(VN_GLOBALS)
         .expect("Cannot access VN globals!");
-    globals.properties.insert(name.to_owned(), value);
+    globals.properties.insert(name, value);
     VnResult::Continue
 }
 
-#[intuicio_function(module_name = "vn", use_context)]
-pub fn delete_global(context: &mut Context, name: VnValue) -> VnResult {
-    let name = name.as_text().expect("`name` is not a text!");
+pub fn delete_global(context: &mut Context, name: String) -> VnResult {
+    let globals = context
+        .custom_mut::<Globals>(VN_GLOBALS)
+        .expect("Cannot access VN globals!");
+    globals.properties.remove(&name);
+    VnResult::Continue
+}
+
+pub fn seed(context: &mut Context, value: i64) -> VnResult {
+    let globals = context
+        .custom_mut::<Globals>(VN_GLOBALS)
+        .expect("Cannot access VN globals!");
+    globals.random.seed(value as u64);
+    VnResult::Continue
+}
+
+pub fn random(context: &mut Context, name: String, min: f64, max: f64, real: bool) -> VnResult {
+    let globals = context
+        .custom_mut::<Globals>(VN_GLOBALS)
+        .expect("Cannot access VN globals!");
+    let value = if real {
+        VnValue::Number(globals.random.range_real(min, max))
+    } else {
+        VnValue::Integer(globals.random.range_integer(min as i64, max as i64))
+    };
+    globals.properties.insert(name, value);
+    VnResult::Continue
+}
+
+pub fn jump_random(
+    context: &mut Context,
+    chapter: Option<String>,
+    labels: Vec<String>,
+    weights: Option<Vec<f64>>,
+) -> VnResult {
+    let weights = weights.unwrap_or_else(|| vec![1.0; labels.len()]);
+    if weights.len() != labels.len() {
+        panic!("`weights` and `labels` have different lengths!");
+    }
     let globals = context
         .custom_mut::<Globals>(VN_GLOBALS)
         .expect("Cannot access VN globals!");
-    globals.properties.remove(name);
+    match globals.random.weighted_index(&weights) {
+        Some(index) => VnResult::JumpTo {
+            chapter,
+            label: Some(labels[index].to_owned()),
+        },
+        None => VnResult::Continue,
+    }
+}
+
+pub fn emit(context: &mut Context, name: String, argument: Option<String>) -> VnResult {
+    let globals = context
+        .custom_mut::<Globals>(VN_GLOBALS)
+        .expect("Cannot access VN globals!");
+    globals.events.push(VnEvent::new(name, argument));
+    VnResult::Continue
+}
+
+/// No language means texts from story are used as they are.
+pub fn set_language(context: &mut Context, language: Option<String>) -> VnResult {
+    let localization = context
+        .custom_mut::<VnLocalization>(VN_LOCALIZATION)
+        .expect("Cannot access VN localization!");
+    localization.set_language(language.as_deref());
     VnResult::Continue
 }
 
@@ -40,8 +96,8 @@ fn validate_query(
         return false;
     }
     if !less_than.is_none() {
-        let result = match (global, less_than) {
-            (VnValue::Number(a), VnValue::Number(b)) => *a < b,
+        let result = match (global.as_number(), less_than.as_number()) {
+            (Some(a), Some(b)) => a < b,
             _ => false,
         };
         if !result {
@@ -49,8 +105,8 @@ fn validate_query(
         }
     }
     if !greater_than.is_none() {
-        let result = match (global, greater_than) {
-            (VnValue::Number(a), VnValue::Number(b)) => *a > b,
+        let result = match (global.as_number(), greater_than.as_number()) {
+            (Some(a), Some(b)) => a > b,
             _ => false,
         };
         if !result {
@@ -185,6 +241,34 @@ pub fn exit(
     VnResult::Exit
 }
 
+pub fn wait(context: &mut Context, seconds: f64, skippable: bool) -> VnResult {
+    let globals = context
+        .custom_mut::<Globals>(VN_GLOBALS)
+        .expect("Cannot access VN globals!");
+    VnResult::Suspend(globals.wait(seconds, skippable))
+}
+
+pub fn spawn(
+    _: &mut Context,
+    thread: String,
+    chapter: Option<String>,
+    label: Option<String>,
+) -> VnResult {
+    VnResult::Spawn {
+        thread,
+        chapter,
+        label,
+    }
+}
+
+pub fn kill(_: &mut Context, thread: String) -> VnResult {
+    VnResult::Kill { thread }
+}
+
+pub fn join(_: &mut Context, thread: String) -> VnResult {
+    VnResult::Join { thread }
+}
+
 pub fn install(registry: &mut Registry) {
     registry.add_struct(define_native_struct! {
         registry => mod vn struct VnValue (VnValue) {}
@@ -192,9 +276,113 @@ pub fn install(registry: &mut Registry) {
     registry.add_struct(define_native_struct! {
         registry => mod vn struct VnResult (VnResult) {}
     });
-    registry.add_function(set_global::define_function(registry));
-    registry.add_function(delete_global::define_function(registry));
-    registry.add_function(jump::define_function(registry));
-    registry.add_function(enter::define_function(registry));
-    registry.add_function(exit::define_function(registry));
+    registry.add_function(
+        VnActionDefinition::new("set_global")
+            .module_name("vn")
+            .doc("Sets global to given value.")
+            .param(VnActionParam::new("name").required())
+            .param("value")
+            .build(registry, set_global),
+    );
+    registry.add_function(
+        VnActionDefinition::new("delete_global")
+            .module_name("vn")
+            .doc("Removes global.")
+            .param(VnActionParam::new("name").required())
+            .build(registry, delete_global),
+    );
+    registry.add_function(
+        VnActionDefinition::new("seed")
+            .module_name("vn")
+            .doc("Reseeds random generator.")
+            .param(VnActionParam::new("value").required())
+            .build(registry, seed),
+    );
+    registry.add_function(
+        VnActionDefinition::new("random")
+            .module_name("vn")
+            .doc("Stores random number from given range in global.")
+            .param(VnActionParam::new("name").required())
+            .param(VnActionParam::new("min").default(VnValue::Number(0.0)))
+            .param(VnActionParam::new("max").default(VnValue::Number(1.0)))
+            .param(
+                VnActionParam::new("real")
+                    .default(VnValue::Boolean(false))
+                    .doc("Produce real number instead of integer."),
+            )
+            .build(registry, random),
+    );
+    registry.add_function(
+        VnActionDefinition::new("jump_random")
+            .module_name("vn")
+            .doc("Jumps to randomly picked label.")
+            .param("chapter")
+            .param(VnActionParam::new("labels").required())
+            .param(VnActionParam::new("weights").doc("Chances of labels, equal if missing."))
+            .build(registry, jump_random),
+    );
+    registry.add_function(
+        VnActionDefinition::new("emit")
+            .module_name("vn")
+            .doc("Dispatches event to chapters triggered by it.")
+            .param(VnActionParam::new("name").required())
+            .param("argument")
+            .build(registry, emit),
+    );
+    registry.add_function(
+        VnActionDefinition::new("set_language")
+            .module_name("vn")
+            .doc("Switches language of localized texts.")
+            .param("language")
+            .build(registry, set_language),
+    );
+    registry.add_function(
+        VnActionDefinition::new("jump")
+            .module_name("vn")
+            .doc("Jumps to label, optionally only when global matches query.")
+            .annotate(jump::define_function(registry)),
+    );
+    registry.add_function(
+        VnActionDefinition::new("enter")
+            .module_name("vn")
+            .doc("Enters chapter, optionally only when global matches query.")
+            .annotate(enter::define_function(registry)),
+    );
+    registry.add_function(
+        VnActionDefinition::new("exit")
+            .module_name("vn")
+            .doc("Exits current chapter, optionally only when global matches query.")
+            .annotate(exit::define_function(registry)),
+    );
+    registry.add_function(
+        VnActionDefinition::new("wait")
+            .module_name("vn")
+            .doc("Suspends story for given amount of story time.")
+            .param(VnActionParam::new("seconds").required())
+            .param(VnActionParam::new("skippable").default(VnValue::Boolean(true)))
+            .build(registry, wait),
+    );
+    registry.add_function(
+        VnActionDefinition::new("spawn")
+            .module_name("vn")
+            .doc("Starts chapter in separate thread.")
+            .param(VnActionParam::new("thread").required())
+            .param("chapter")
+            .param("label")
+            .build(registry, spawn),
+    );
+    registry.add_function(
+        VnActionDefinition::new("kill")
+            .module_name("vn")
+            .doc("Stops thread.")
+            .param(VnActionParam::new("thread").required())
+            .build(registry, kill),
+    );
+    registry.add_function(
+        VnActionDefinition::new("join")
+            .module_name("vn")
+            .doc("Waits for thread to complete.")
+            .param(VnActionParam::new("thread").required())
+            .build(registry, join),
+    );
 }
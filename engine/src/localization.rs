@@ -0,0 +1,234 @@
+use crate::script::{VnAction, VnChapterItem, VnStory, VnValue};
+use intuicio_essentials::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+    fmt::{Display, Formatter},
+};
+
+pub const VN_LOCALIZATION: &str = "vn-localization";
+
+/// Translated texts of single language, keyed by text ids (see [`crate::script::VnTextId`]).
+///
+/// Table files contain `id = text` lines, text can be quoted to use escapes,
+/// lines starting with `#` are comments.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VnLocale {
+    pub texts: HashMap<String, String>,
+}
+
+impl VnLocale {
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let mut result = Self::default();
+        for (index, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (id, text) = line
+                .split_once('=')
+                .ok_or_else(|| format!("Expected `id = text` at line {}", index + 1))?;
+            let text = text.trim();
+            let text = if text.starts_with('"') {
+                snailquote::unescape(text)
+                    .map_err(|error| format!("{} at line {}", error, index + 1))?
+            } else {
+                text.to_owned()
+            };
+            result.texts.insert(id.trim().to_owned(), text);
+        }
+        Ok(result)
+    }
+
+    /// Table of all localizable texts of a story, in their original language.
+    pub fn template(story: &VnStory) -> Self {
+        let mut result = Self::default();
+        for chapter in story.chapters.values() {
+            for item in &chapter.items {
+                if let VnChapterItem::Action(action) = item {
+                    for text in &action.texts {
+                        let value = action.params.get(&text.param);
+                        let value = match text.index {
+                            Some(index) => value
+                                .and_then(|value| value.as_array())
+                                .and_then(|items| items.get(index)),
+                            None => value,
+                        };
+                        if let Some(value) = value.and_then(|value| value.as_text()) {
+                            result.texts.insert(text.id.to_owned(), value.to_owned());
+                        }
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    pub fn text(&self, id: &str) -> Option<&str> {
+        self.texts.get(id).map(|text| text.as_str())
+    }
+
+    /// Action with its text params replaced by translations, if any of them is translated.
+    pub fn localize(&self, action: &VnAction) -> Option<VnAction> {
+        let mut result = None;
+        for text in &action.texts {
+            let translation = match self.text(&text.id) {
+                Some(translation) => VnValue::Text(translation.to_owned()),
+                None => continue,
+            };
+            let value = result
+                .get_or_insert_with(|| action.to_owned())
+                .params
+                .get_mut(&text.param);
+            let value = match text.index {
+                Some(index) => value.and_then(|value| match value {
+                    VnValue::Array(items) => items.get_mut(index),
+                    _ => None,
+                }),
+                None => value,
+            };
+            if let Some(value) = value {
+                *value = translation;
+            }
+        }
+        result
+    }
+}
+
+impl Display for VnLocale {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (id, text) in self.texts.iter().collect::<BTreeMap<_, _>>() {
+            writeln!(f, "{} = {:?}", id, text)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct VnLocaleContentParser;
+
+impl BytesContentParser<VnLocale> for VnLocaleContentParser {
+    fn parse(&self, bytes: Vec<u8>) -> Result<VnLocale, Box<dyn Error>> {
+        let content = String::from_utf8(bytes)?;
+        Ok(VnLocale::parse(&content)?)
+    }
+}
+
+/// Locale tables of all languages and currently selected language.
+/// No language selected means texts from story are used as they are.
+#[derive(Debug, Default, Clone)]
+pub struct VnLocalization {
+    pub locales: HashMap<String, VnLocale>,
+    language: Option<String>,
+}
+
+impl VnLocalization {
+    /// Loads locale table file and merges it into given language.
+    pub fn load<CP>(
+        &mut self,
+        language: &str,
+        path: &str,
+        content_provider: &mut CP,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        CP: ScriptContentProvider<VnLocale>,
+    {
+        let path = content_provider.sanitize_path(path)?;
+        for content in content_provider.unpack_load(&path)? {
+            if let Some(locale) = content.data? {
+                self.locales
+                    .entry(language.to_owned())
+                    .or_default()
+                    .texts
+                    .extend(locale.texts);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn language(&self) -> Option<&str> {
+        self.language.as_deref()
+    }
+
+    pub fn set_language(&mut self, language: Option<&str>) {
+        self.language = language.map(|language| language.to_owned());
+    }
+
+    pub fn locale(&self) -> Option<&VnLocale> {
+        self.locales.get(self.language.as_ref()?)
+    }
+
+    pub fn text(&self, id: &str) -> Option<&str> {
+        self.locale()?.text(id)
+    }
+
+    pub fn localize(&self, action: &VnAction) -> Option<VnAction> {
+        self.locale()?.localize(action)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::script::VnFile;
+
+    #[test]
+    fn test_localization() {
+        let story = VnFile::parse(
+            r#"
+            chapter main {
+                say who: rin what: "Hello!" choices: ["Yes" "No"]
+                set_global name: greeting value: "Good morning"
+            }
+            "#,
+        )
+        .unwrap()
+        .story;
+        let template = VnLocale::template(&story);
+        assert_eq!(template.text("main.296cd8ef.what"), Some("Hello!"));
+        assert_eq!(template.text("main.296cd8ef.choices.1"), Some("No"));
+        assert_eq!(template.text("main.8976a3ae.value"), Some("Good morning"));
+        assert_eq!(VnLocale::parse(&template.to_string()).unwrap(), template);
+
+        let mut localization = VnLocalization::default();
+        localization.locales.insert(
+            "pl".to_owned(),
+            VnLocale::parse(
+                r#"
+                # polish
+                main.296cd8ef.what = "Cze\u{15b}\u{107}!"
+                main.296cd8ef.choices.0 = Tak
+                main.8976a3ae.value = "Dzie\u{144} dobry"
+                "#,
+            )
+            .unwrap(),
+        );
+        let action = match &story.chapters.get("main").unwrap().items[0] {
+            VnChapterItem::Action(action) => action,
+            _ => unreachable!(),
+        };
+        assert!(localization.localize(action).is_none());
+        localization.set_language(Some("pl"));
+        let action = localization.localize(action).unwrap();
+        assert_eq!(action.params.get("what").unwrap().as_text(), Some("Cześć!"));
+        assert_eq!(
+            action.params.get("choices").unwrap(),
+            &VnValue::Array(vec![
+                VnValue::Text("Tak".to_owned()),
+                VnValue::Text("No".to_owned())
+            ])
+        );
+        assert_eq!(action.params.get("who").unwrap().as_text(), Some("rin"));
+        let action = match &story.chapters.get("main").unwrap().items[1] {
+            VnChapterItem::Action(action) => action,
+            _ => unreachable!(),
+        };
+        assert_eq!(action.id, None);
+        let action = localization.localize(action).unwrap();
+        assert_eq!(
+            action.params.get("value").unwrap().as_text(),
+            Some("Dzień dobry")
+        );
+        assert_eq!(template.text("main.296cd8ef.who"), None);
+    }
+}
@@ -0,0 +1,513 @@
+use crate::{
+    convert::{from_vn_properties, VnValueError},
+    script::{VnStory, VnValue},
+};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter, Write};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Typewriter reveal pace, configured in [`VnStory::DIALOGUE_CONFIG`] config.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VnTextSpeed {
+    /// Graphemes per second.
+    #[serde(rename = "text_speed")]
+    pub chars_per_second: f64,
+    /// Extra seconds after sentence end, like `.`, `?` or `。`.
+    pub sentence_pause: f64,
+    /// Extra seconds after clause end, like `,`, `;` or `、`.
+    pub clause_pause: f64,
+}
+
+impl Default for VnTextSpeed {
+    fn default() -> Self {
+        Self {
+            chars_per_second: 30.0,
+            sentence_pause: 0.3,
+            clause_pause: 0.1,
+        }
+    }
+}
+
+impl VnTextSpeed {
+    pub fn from_story(story: &VnStory) -> Result<Self, VnValueError> {
+        match story.configs.get(VnStory::DIALOGUE_CONFIG) {
+            Some(config) => from_vn_properties(
+                &format!("config.{}", VnStory::DIALOGUE_CONFIG),
+                &config.properties,
+            ),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Seconds of grapheme reveal with given speed multiplier, zero reveals instantly.
+    pub fn grapheme_duration(&self, speed: f64) -> f64 {
+        if speed > 0.0 && self.chars_per_second > 0.0 {
+            1.0 / (self.chars_per_second * speed)
+        } else {
+            0.0
+        }
+    }
+
+    /// Extra seconds after punctuation grapheme.
+    /// Latin punctuation pauses only when followed by whitespace, so `...` or `?!` pause once,
+    /// while full-width one always does. Nothing pauses at the end of text.
+    pub fn pause(&self, grapheme: &str, next: Option<&str>) -> f64 {
+        let Some(next) = next else {
+            return 0.0;
+        };
+        let (sentence, full_width) = match grapheme {
+            "." | "!" | "?" | "\u{2026}" => (true, false),
+            "\u{3002}" | "\u{ff01}" | "\u{ff1f}" => (true, true),
+            "," | ";" | ":" => (false, false),
+            "\u{3001}" | "\u{ff0c}" | "\u{ff1b}" | "\u{ff1a}" => (false, true),
+            _ => return 0.0,
+        };
+        if !full_width && !next.starts_with(char::is_whitespace) {
+            0.0
+        } else if sentence {
+            self.sentence_pause
+        } else {
+            self.clause_pause
+        }
+    }
+}
+
+/// Visual style of text span, nested tags accumulate.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VnTextStyle {
+    pub bold: bool,
+    pub italic: bool,
+    /// `0xRRGGBBAA`, renderer decides when not set.
+    pub color: Option<u32>,
+    /// Font size, renderer decides when not set.
+    pub size: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum VnSpan {
+    Text {
+        text: String,
+        style: VnTextStyle,
+        /// Reveal speed multiplier, zero reveals instantly.
+        speed: f64,
+    },
+    /// Reveal pause in seconds.
+    Wait(f64),
+}
+
+/// Dialog text with inline markup tags:
+/// - `{b}...{/b}` - bold,
+/// - `{i}...{/i}` - italics,
+/// - `{color=#rrggbb}...{/color}` - color in `RGB`, `RRGGBB` or `RRGGBBAA` hex digits,
+/// - `{size=40}...{/size}` - font size,
+/// - `{speed=2}...{/speed}` - reveal speed multiplier,
+/// - `{w=0.5}` - reveal pause in seconds,
+/// - `{{` and `}}` - literal braces.
+///
+/// Unknown and malformed tags are left verbatim.
+/// Displays back as markup, so revealed fragments can be passed around as text.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VnMarkup {
+    pub spans: Vec<VnSpan>,
+}
+
+struct RevealStep {
+    span: usize,
+    /// Byte index in span text right after grapheme.
+    end: usize,
+    duration: f64,
+}
+
+/// Style change introduced by opening tag.
+#[derive(Debug, Clone, Copy)]
+enum Tag {
+    Bold,
+    Italic,
+    Color(u32),
+    Size(f64),
+    Speed(f64),
+}
+
+impl VnMarkup {
+    /// Whether content of `{tag}` names markup tag, so it is not message placeholder.
+    pub fn is_tag(tag: &str) -> bool {
+        match tag.split_once('=') {
+            Some((name, _)) => matches!(name.trim(), "color" | "size" | "speed" | "w"),
+            None => matches!(tag.trim(), "b" | "i") || tag.trim().starts_with('/'),
+        }
+    }
+
+    pub fn parse(text: &str) -> Self {
+        let mut result = Self::default();
+        let mut stack = Vec::<(&str, Tag)>::new();
+        let mut rest = text;
+        while let Some(index) = rest.find(['{', '}']) {
+            result.push_tags(&rest[..index], &stack);
+            rest = &rest[index..];
+            if rest.starts_with("{{") || rest.starts_with("}}") {
+                result.push_tags(&rest[..1], &stack);
+                rest = &rest[2..];
+                continue;
+            }
+            let Some(tag) = rest[1..]
+                .find(['{', '}'])
+                .filter(|end| rest.as_bytes()[end + 1] == b'}')
+                .map(|end| &rest[1..end + 1])
+            else {
+                result.push_tags(&rest[..1], &stack);
+                rest = &rest[1..];
+                continue;
+            };
+            let (name, value) = match tag.split_once('=') {
+                Some((name, value)) => (name.trim(), Some(value.trim())),
+                None => (tag.trim(), None),
+            };
+            let number = value.and_then(|value| value.parse::<f64>().ok());
+            let opened = match (name, value) {
+                ("b", None) => Some(Tag::Bold),
+                ("i", None) => Some(Tag::Italic),
+                ("color", Some(value)) => VnValue::hex(value)
+                    .and_then(|value| value.as_color())
+                    .map(Tag::Color),
+                ("size", Some(_)) => number.filter(|size| *size > 0.0).map(Tag::Size),
+                ("speed", Some(_)) => number.filter(|speed| *speed >= 0.0).map(Tag::Speed),
+                _ => None,
+            };
+            let accepted = if let Some(opened) = opened {
+                stack.push((name, opened));
+                true
+            } else if let Some(seconds) = number.filter(|_| name == "w") {
+                result.spans.push(VnSpan::Wait(seconds.max(0.0)));
+                true
+            } else if let Some(index) = name
+                .strip_prefix('/')
+                .filter(|_| value.is_none())
+                .and_then(|name| stack.iter().rposition(|(tag, _)| *tag == name))
+            {
+                stack.remove(index);
+                true
+            } else {
+                false
+            };
+            if !accepted {
+                result.push_tags(&rest[..tag.len() + 2], &stack);
+            }
+            rest = &rest[tag.len() + 2..];
+        }
+        result.push_tags(rest, &stack);
+        result
+    }
+
+    /// Text without any markup.
+    pub fn plain_text(&self) -> String {
+        self.spans
+            .iter()
+            .filter_map(|span| match span {
+                VnSpan::Text { text, .. } => Some(text.as_str()),
+                VnSpan::Wait(_) => None,
+            })
+            .collect()
+    }
+
+    /// Seconds it takes to reveal whole text.
+    pub fn duration(&self, speed: &VnTextSpeed) -> f64 {
+        let waits = self
+            .spans
+            .iter()
+            .filter_map(|span| match span {
+                VnSpan::Wait(seconds) => Some(*seconds),
+                VnSpan::Text { .. } => None,
+            })
+            .sum::<f64>();
+        waits
+            + self
+                .steps(speed)
+                .iter()
+                .map(|step| step.duration)
+                .sum::<f64>()
+    }
+
+    /// Splits into revealed and remaining parts after given seconds of typewriter reveal.
+    /// Text is revealed by whole graphemes.
+    pub fn split_at_time(&self, time: f64, speed: &VnTextSpeed) -> (Self, Self) {
+        let mut revealed = Self::default();
+        let mut remaining = Self::default();
+        let mut steps = self.steps(speed).into_iter().peekable();
+        let mut elapsed = 0.0;
+        let mut stopped = false;
+        for (index, span) in self.spans.iter().enumerate() {
+            match span {
+                VnSpan::Text { text, style, speed } => {
+                    let mut split = 0;
+                    while let Some(step) = steps.next_if(|step| step.span == index) {
+                        // Instant graphemes show up as soon as reveal reaches them.
+                        stopped =
+                            stopped || elapsed > time || (elapsed == time && step.duration > 0.0);
+                        if !stopped {
+                            split = step.end;
+                        }
+                        elapsed += step.duration;
+                    }
+                    revealed.push_text(&text[..split], style, *speed);
+                    remaining.push_text(&text[split..], style, *speed);
+                }
+                VnSpan::Wait(seconds) => {
+                    let passed = if stopped {
+                        0.0
+                    } else {
+                        (time - elapsed).clamp(0.0, *seconds)
+                    };
+                    if passed > 0.0 {
+                        revealed.spans.push(VnSpan::Wait(passed));
+                    }
+                    if passed < *seconds {
+                        stopped = true;
+                        remaining.spans.push(VnSpan::Wait(seconds - passed));
+                    }
+                    elapsed += seconds;
+                }
+            }
+        }
+        (revealed, remaining)
+    }
+
+    /// Splits into revealed and remaining parts at factor of whole reveal duration.
+    pub fn split_at_factor(&self, factor: f64, speed: &VnTextSpeed) -> (Self, Self) {
+        let duration = self.duration(speed);
+        self.split_at_time(factor.clamp(0.0, 1.0) * duration, speed)
+    }
+
+    /// Graphemes of text spans with their reveal time, including pause after them.
+    fn steps(&self, speed: &VnTextSpeed) -> Vec<RevealStep> {
+        let graphemes = self
+            .spans
+            .iter()
+            .enumerate()
+            .filter_map(|(index, span)| match span {
+                VnSpan::Text { text, speed, .. } => Some((index, text, *speed)),
+                VnSpan::Wait(_) => None,
+            })
+            .flat_map(|(index, text, multiplier)| {
+                text.graphemes(true).scan(0, move |end, grapheme| {
+                    *end += grapheme.len();
+                    Some((index, *end, grapheme, multiplier))
+                })
+            })
+            .collect::<Vec<_>>();
+        graphemes
+            .iter()
+            .enumerate()
+            .map(|(position, (span, end, grapheme, multiplier))| {
+                let mut duration = speed.grapheme_duration(*multiplier);
+                if duration > 0.0 {
+                    let next = graphemes.get(position + 1).map(|(_, _, next, _)| *next);
+                    duration += speed.pause(grapheme, next);
+                }
+                RevealStep {
+                    span: *span,
+                    end: *end,
+                    duration,
+                }
+            })
+            .collect()
+    }
+
+    fn push_tags(&mut self, value: &str, stack: &[(&str, Tag)]) {
+        let mut style = VnTextStyle::default();
+        let mut speed = 1.0;
+        for (_, tag) in stack {
+            match *tag {
+                Tag::Bold => style.bold = true,
+                Tag::Italic => style.italic = true,
+                Tag::Color(color) => style.color = Some(color),
+                Tag::Size(size) => style.size = Some(size),
+                Tag::Speed(value) => speed = value,
+            }
+        }
+        self.push_text(value, &style, speed);
+    }
+
+    fn push_text(&mut self, value: &str, style: &VnTextStyle, speed: f64) {
+        if value.is_empty() {
+            return;
+        }
+        if let Some(VnSpan::Text {
+            text,
+            style: last_style,
+            speed: last_speed,
+        }) = self.spans.last_mut()
+        {
+            if last_style == style && *last_speed == speed {
+                text.push_str(value);
+                return;
+            }
+        }
+        self.spans.push(VnSpan::Text {
+            text: value.to_owned(),
+            style: style.to_owned(),
+            speed,
+        });
+    }
+}
+
+impl Display for VnMarkup {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for span in &self.spans {
+            match span {
+                VnSpan::Text { text, style, speed } => {
+                    let mut closing = vec![];
+                    if style.bold {
+                        f.write_str("{b}")?;
+                        closing.push("{/b}");
+                    }
+                    if style.italic {
+                        f.write_str("{i}")?;
+                        closing.push("{/i}");
+                    }
+                    if let Some(color) = style.color {
+                        write!(f, "{{color=#{:08x}}}", color)?;
+                        closing.push("{/color}");
+                    }
+                    if let Some(size) = style.size {
+                        write!(f, "{{size={}}}", size)?;
+                        closing.push("{/size}");
+                    }
+                    if *speed != 1.0 {
+                        write!(f, "{{speed={}}}", speed)?;
+                        closing.push("{/speed}");
+                    }
+                    for c in text.chars() {
+                        match c {
+                            '{' => f.write_str("{{")?,
+                            '}' => f.write_str("}}")?,
+                            c => f.write_char(c)?,
+                        }
+                    }
+                    for tag in closing.into_iter().rev() {
+                        f.write_str(tag)?;
+                    }
+                }
+                VnSpan::Wait(seconds) => write!(f, "{{w={}}}", seconds)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_markup() {
+        let markup = VnMarkup::parse("Hi {b}{color=#f00}Rin{/color}!{/b}{w=0.5} {x} {{ok}} {/i}");
+        assert_eq!(
+            markup.spans,
+            vec![
+                VnSpan::Text {
+                    text: "Hi ".to_owned(),
+                    style: VnTextStyle::default(),
+                    speed: 1.0,
+                },
+                VnSpan::Text {
+                    text: "Rin".to_owned(),
+                    style: VnTextStyle {
+                        bold: true,
+                        color: Some(0xff0000ff),
+                        ..Default::default()
+                    },
+                    speed: 1.0,
+                },
+                VnSpan::Text {
+                    text: "!".to_owned(),
+                    style: VnTextStyle {
+                        bold: true,
+                        ..Default::default()
+                    },
+                    speed: 1.0,
+                },
+                VnSpan::Wait(0.5),
+                VnSpan::Text {
+                    text: " {x} {ok} {/i}".to_owned(),
+                    style: VnTextStyle::default(),
+                    speed: 1.0,
+                },
+            ]
+        );
+        assert_eq!(markup.plain_text(), "Hi Rin! {x} {ok} {/i}");
+        assert_eq!(VnMarkup::parse(&markup.to_string()), markup);
+        assert_eq!(
+            VnMarkup::parse("{size=40}{i}big{/size} plain{/i} {size=-1}{w=x}{color=red}").spans,
+            vec![
+                VnSpan::Text {
+                    text: "big".to_owned(),
+                    style: VnTextStyle {
+                        italic: true,
+                        size: Some(40.0),
+                        ..Default::default()
+                    },
+                    speed: 1.0,
+                },
+                VnSpan::Text {
+                    text: " plain".to_owned(),
+                    style: VnTextStyle {
+                        italic: true,
+                        ..Default::default()
+                    },
+                    speed: 1.0,
+                },
+                VnSpan::Text {
+                    text: " {size=-1}{w=x}{color=red}".to_owned(),
+                    style: VnTextStyle::default(),
+                    speed: 1.0,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_markup_reveal() {
+        let speed = VnTextSpeed {
+            chars_per_second: 1.0,
+            sentence_pause: 2.0,
+            clause_pause: 1.0,
+        };
+        let markup = VnMarkup::parse("ab{w=1}{speed=2}cd{/speed}{speed=0}ef{/speed}");
+        assert_eq!(markup.duration(&speed), 4.0);
+        let (revealed, remaining) = markup.split_at_time(0.0, &speed);
+        assert_eq!(revealed.plain_text(), "");
+        assert_eq!(remaining.to_string(), markup.to_string());
+        let (revealed, remaining) = markup.split_at_time(2.5, &speed);
+        assert_eq!(revealed.to_string(), "ab{w=0.5}");
+        assert_eq!(
+            remaining.to_string(),
+            "{w=0.5}{speed=2}cd{/speed}{speed=0}ef{/speed}"
+        );
+        let (revealed, remaining) = markup.split_at_time(3.5, &speed);
+        assert_eq!(revealed.plain_text(), "abc");
+        assert_eq!(remaining.plain_text(), "def");
+        let (revealed, remaining) = markup.split_at_factor(1.0, &speed);
+        assert_eq!(revealed, markup);
+        assert!(remaining.spans.is_empty());
+
+        let markup = VnMarkup::parse("Cześć... Tak, ok!");
+        assert_eq!(markup.duration(&speed), 20.0);
+        assert_eq!(markup.split_at_time(3.5, &speed).0.plain_text(), "Cześ");
+        assert_eq!(markup.split_at_time(8.5, &speed).0.plain_text(), "Cześć...");
+        assert_eq!(
+            markup.split_at_time(10.5, &speed).0.plain_text(),
+            "Cześć... "
+        );
+        let markup = VnMarkup::parse("こんにちは。元気？");
+        assert_eq!(markup.duration(&speed), 11.0);
+        assert_eq!(
+            markup.split_at_time(7.5, &speed).0.plain_text(),
+            "こんにちは。"
+        );
+        assert_eq!(
+            markup.split_at_time(8.5, &speed).0.plain_text(),
+            "こんにちは。元"
+        );
+    }
+}
@@ -57,11 +57,37 @@ fn parse_file(pair: Pair<Rule>) -> VnFile {
             rule => unreachable!("Unsupported: {:?}", rule),
         }
     }
+    result.story.resolve_shorthands();
     result
 }
 
-fn parse_import(pair: Pair<Rule>) -> String {
-    parse_text(pair.into_inner().next().unwrap())
+fn parse_import(pair: Pair<Rule>) -> VnDependency {
+    let mut optional = false;
+    let mut kind = None;
+    for pair in pair.into_inner() {
+        match pair.as_rule() {
+            Rule::import_optional => optional = true,
+            Rule::import_kind => {
+                kind = Some(match pair.as_str() {
+                    "script" => VnDependencyKind::Script,
+                    "plugin" => VnDependencyKind::Plugin,
+                    "simpleton" => VnDependencyKind::Simpleton,
+                    "assets" => VnDependencyKind::Assets,
+                    kind => unreachable!("Unsupported import kind: {}", kind),
+                })
+            }
+            Rule::text => {
+                let path = parse_text(pair);
+                return VnDependency {
+                    kind: kind.unwrap_or_else(|| VnDependencyKind::of_path(&path)),
+                    path,
+                    optional,
+                };
+            }
+            rule => unreachable!("Unsupported: {:?}", rule),
+        }
+    }
+    unreachable!()
 }
 
 fn parse_chapter(pair: Pair<Rule>) -> (String, VnChapter) {
@@ -69,15 +95,23 @@ fn parse_chapter(pair: Pair<Rule>) -> (String, VnChapter) {
     let name = parse_identifier(pairs.next().unwrap());
     let mut result = VnChapter::default();
     for pair in pairs {
+        if pair.as_rule() == Rule::chapter_trigger {
+            result.triggers.push(parse_chapter_trigger(pair));
+            continue;
+        }
         let pair = pair.into_inner().next().unwrap();
+        result.lines.push(pair.as_span().start_pos().line_col().0);
         match pair.as_rule() {
             Rule::label => {
                 result.items.push(VnChapterItem::Label(parse_label(pair)));
             }
-            Rule::chapter_action => {
-                result
-                    .items
-                    .push(VnChapterItem::Action(parse_chapter_action(pair)));
+            Rule::chapter_dialogue | Rule::chapter_action => {
+                let action = if pair.as_rule() == Rule::chapter_dialogue {
+                    parse_chapter_dialogue(pair)
+                } else {
+                    parse_chapter_action(pair)
+                };
+                result.items.push(VnChapterItem::Action(action));
             }
             rule => unreachable!("Unsupported: {:?}", rule),
         }
@@ -85,6 +119,13 @@ fn parse_chapter(pair: Pair<Rule>) -> (String, VnChapter) {
     (name, result)
 }
 
+fn parse_chapter_trigger(pair: Pair<Rule>) -> VnTrigger {
+    let mut pairs = pair.into_inner();
+    let event = parse_identifier(pairs.next().unwrap());
+    let argument = pairs.next().map(parse_identifier);
+    VnTrigger { event, argument }
+}
+
 fn parse_label(pair: Pair<Rule>) -> String {
     parse_identifier(pair.into_inner().next().unwrap())
 }
@@ -92,11 +133,77 @@ fn parse_label(pair: Pair<Rule>) -> String {
 fn parse_chapter_action(pair: Pair<Rule>) -> VnAction {
     let mut pairs = pair.into_inner();
     let (name, module_name) = parse_chapter_action_path(pairs.next().unwrap());
-    let params = pairs.map(parse_property).collect();
+    let mut params = HashMap::new();
+    let mut literals = vec![];
+    parse_chapter_action_params(pairs, &mut params, &mut literals);
     VnAction {
         name,
         module_name,
         params,
+        id: None,
+        texts: vec![],
+        literals,
+        shorthand: false,
+    }
+}
+
+/// `who "what"` line becomes dialogue action, later pointed at configured one
+/// by [`VnStory::resolve_shorthands`].
+fn parse_chapter_dialogue(pair: Pair<Rule>) -> VnAction {
+    let mut params = HashMap::new();
+    let mut literals = vec![];
+    let mut pairs = pair.into_inner().peekable();
+    if let Some(pair) = pairs.next_if(|pair| pair.as_rule() == Rule::identifier) {
+        params.insert(
+            VnAction::WHO_PARAM.to_owned(),
+            VnValue::Text(parse_identifier(pair)),
+        );
+    }
+    params.insert(
+        VnAction::WHAT_PARAM.to_owned(),
+        VnValue::Text(parse_text(pairs.next().unwrap())),
+    );
+    literals.push((VnAction::WHAT_PARAM.to_owned(), None));
+    parse_chapter_action_params(pairs, &mut params, &mut literals);
+    VnAction {
+        name: VnAction::DIALOGUE.to_owned(),
+        module_name: None,
+        params,
+        id: None,
+        texts: vec![],
+        literals,
+        shorthand: true,
+    }
+}
+
+fn parse_chapter_action_params<'a>(
+    pairs: impl Iterator<Item = Pair<'a, Rule>>,
+    params: &mut HashMap<String, VnValue>,
+    literals: &mut Vec<(String, Option<usize>)>,
+) {
+    for pair in pairs {
+        let (param, value) = parse_property(pair.clone());
+        if param != VnAction::ID_PARAM {
+            let value_pair = pair
+                .into_inner()
+                .nth(1)
+                .unwrap()
+                .into_inner()
+                .next()
+                .unwrap();
+            match value_pair.as_rule() {
+                Rule::text => literals.push((param.to_owned(), None)),
+                Rule::array => {
+                    for (index, item) in value_pair.into_inner().enumerate() {
+                        if item.into_inner().next().unwrap().as_rule() == Rule::text {
+                            literals.push((param.to_owned(), Some(index)));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        params.insert(param, value);
     }
 }
 
@@ -142,10 +249,38 @@ fn parse_value(pair: Pair<Rule>) -> VnValue {
     match pair.as_rule() {
         Rule::none => VnValue::None,
         Rule::text => VnValue::Text(parse_text(pair)),
-        Rule::number => VnValue::Number(parse_number(pair)),
+        Rule::real => VnValue::Number(parse_real(pair)),
+        Rule::integer => VnValue::Integer(parse_integer(pair)),
         Rule::color => VnValue::Color(parse_color(pair)),
         Rule::bool_true => VnValue::Boolean(true),
         Rule::bool_false => VnValue::Boolean(false),
+        Rule::vec2 => {
+            let mut pairs = pair.into_inner().map(parse_real);
+            VnValue::Vec2 {
+                x: pairs.next().unwrap(),
+                y: pairs.next().unwrap(),
+            }
+        }
+        Rule::rect => {
+            let mut pairs = pair.into_inner().map(parse_real);
+            VnValue::Rect {
+                x: pairs.next().unwrap(),
+                y: pairs.next().unwrap(),
+                width: pairs.next().unwrap(),
+                height: pairs.next().unwrap(),
+            }
+        }
+        Rule::reference => {
+            let mut pairs = pair.into_inner();
+            let kind = match pairs.next().unwrap().as_str() {
+                "chapter" => VnReferenceKind::Chapter,
+                "character" => VnReferenceKind::Character,
+                "scene" => VnReferenceKind::Scene,
+                kind => unreachable!("Unsupported reference: {}", kind),
+            };
+            let name = parse_identifier(pairs.next().unwrap());
+            VnValue::Reference { kind, name }
+        }
         Rule::map => VnValue::Map(parse_map(pair)),
         Rule::array => VnValue::Array(parse_array(pair)),
         Rule::identifier => VnValue::Text(parse_identifier(pair)),
@@ -161,16 +296,99 @@ fn parse_map(pair: Pair<Rule>) -> HashMap<String, VnValue> {
     pair.into_inner().map(parse_property).collect()
 }
 
+/// Normalizes color literal into `0xRRGGBBAA` layout.
+/// `rgba` channels, alpha included, are in 0-255 range like hex digits.
 fn parse_color(pair: Pair<Rule>) -> u32 {
-    u32::from_str_radix(pair.as_str(), 16).unwrap()
+    let pair = pair.into_inner().next().unwrap();
+    match pair.as_rule() {
+        Rule::color_hex => {
+            let digits = pair.into_inner().next().unwrap().as_str();
+            VnValue::hex(digits)
+                .and_then(|value| value.as_color())
+                .unwrap()
+        }
+        Rule::color_rgba => {
+            let mut pairs = pair.into_inner();
+            let mut channel = || {
+                let pair = pairs.next().unwrap();
+                parse_real(pair).clamp(0.0, 255.0).round() as u8
+            };
+            u32::from_be_bytes([channel(), channel(), channel(), channel()])
+        }
+        _ => unreachable!(),
+    }
 }
 
-fn parse_number(pair: Pair<Rule>) -> f64 {
+fn parse_real(pair: Pair<Rule>) -> f64 {
     pair.as_str().parse::<f64>().unwrap()
 }
 
+fn parse_integer(pair: Pair<Rule>) -> i64 {
+    pair.as_str().parse::<i64>().unwrap()
+}
+
 fn parse_text(pair: Pair<Rule>) -> String {
-    snailquote::unescape(pair.as_str()).unwrap()
+    match pair.clone().into_inner().next() {
+        Some(block) if block.as_rule() == Rule::text_block => parse_text_block(block),
+        _ => snailquote::unescape(pair.as_str()).unwrap(),
+    }
+}
+
+/// Triple-quoted text is taken as it is, without escapes. Line break after opening quotes,
+/// blank line before closing quotes and indentation common to all lines get stripped.
+/// Folded text (`>"""`) joins lines with spaces, blank lines separating paragraphs.
+fn parse_text_block(pair: Pair<Rule>) -> String {
+    let mut pairs = pair.into_inner();
+    let mut pair = pairs.next().unwrap();
+    let fold = pair.as_rule() == Rule::text_block_fold;
+    if fold {
+        pair = pairs.next().unwrap();
+    }
+    let content = pair.as_str();
+    let content = content
+        .strip_prefix("\r\n")
+        .or_else(|| content.strip_prefix('\n'))
+        .unwrap_or(content);
+    let mut lines = content.lines().collect::<Vec<_>>();
+    if lines
+        .last()
+        .map(|line| line.trim().is_empty())
+        .unwrap_or_default()
+    {
+        lines.pop();
+    }
+    let indentation = |line: &str| line.len() - line.trim_start_matches([' ', '\t']).len();
+    let indent = lines
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| indentation(line))
+        .min()
+        .unwrap_or_default();
+    let lines = lines.into_iter().map(|line| {
+        if line.trim().is_empty() {
+            ""
+        } else {
+            &line[indent..]
+        }
+    });
+    if !fold {
+        return lines.collect::<Vec<_>>().join("\n");
+    }
+    let mut result = String::new();
+    let mut paragraph_start = true;
+    for line in lines {
+        if line.is_empty() {
+            result.push('\n');
+            paragraph_start = true;
+        } else {
+            if !paragraph_start {
+                result.push(' ');
+            }
+            result.push_str(line.trim_end());
+            paragraph_start = false;
+        }
+    }
+    result
 }
 
 fn parse_identifier(pair: Pair<Rule>) -> String {
@@ -186,4 +404,273 @@ mod tests {
         let content = std::fs::read_to_string("../resources/main.vns").unwrap();
         parse(&content).unwrap();
     }
+
+    #[test]
+    fn test_chapter_triggers() {
+        let file = parse(
+            r#"
+            chapter autosave on chapter_enter on global_changed score on custom {
+                exit
+            }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            file.story.chapters.get("autosave").unwrap().triggers,
+            vec![
+                VnTrigger {
+                    event: "chapter_enter".to_owned(),
+                    argument: None,
+                },
+                VnTrigger {
+                    event: "global_changed".to_owned(),
+                    argument: Some("score".to_owned()),
+                },
+                VnTrigger {
+                    event: "custom".to_owned(),
+                    argument: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dialogue_ids() {
+        let ids = |content: &str| {
+            parse(content)
+                .unwrap()
+                .story
+                .chapters
+                .get("intro")
+                .unwrap()
+                .items
+                .iter()
+                .filter_map(|item| match item {
+                    VnChapterItem::Action(action) => action.id.to_owned(),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+        let original = ids(r#"
+            chapter intro {
+                say what: "a"
+                show character: rin
+                say what: "b"
+            $next:
+                say what: "c"
+                say what: "c"
+            }
+            "#);
+        assert_eq!(
+            original,
+            vec![
+                "intro.6c8f2bf4",
+                "intro.6f8f30ad",
+                "intro.next.6e8f2f1a",
+                "intro.next.6e8f2f1a-2"
+            ]
+        );
+        let edited = ids(r#"
+            chapter intro {
+                say what: "new"
+                say what: "a"
+                say what: "b"
+            $next:
+                say what: "c"
+                say what: "c"
+            }
+            "#);
+        assert_eq!(edited[1..], original);
+    }
+
+    #[test]
+    fn test_values() {
+        let file = parse(
+            r#"
+            config values {
+                integer: 42
+                real: -1.5
+                position: vec2(1, 0.5)
+                area: rect(0, 0, 100, 50)
+                entry: chapter(intro)
+                short: #f0a
+                opaque: #ff8000
+                translucent: #ff800080
+                channels: rgba(255, 128, 0, 128)
+                reals: rgba(255.0, 127.6, 0, 255.0) # same scale as integers
+                #comment
+                decade: #dec
+                palette: [#fff # comment
+                    #abc
+                    #000]
+            }
+            "#,
+        )
+        .unwrap();
+        let properties = &file.story.configs.get("values").unwrap().properties;
+        assert!(matches!(
+            properties.get("integer"),
+            Some(VnValue::Integer(42))
+        ));
+        assert_eq!(properties.get("real"), Some(&VnValue::Number(-1.5)));
+        assert_eq!(
+            properties.get("position"),
+            Some(&VnValue::Vec2 { x: 1.0, y: 0.5 })
+        );
+        assert_eq!(
+            properties.get("area"),
+            Some(&VnValue::Rect {
+                x: 0.0,
+                y: 0.0,
+                width: 100.0,
+                height: 50.0
+            })
+        );
+        assert_eq!(
+            properties.get("entry"),
+            Some(&VnValue::Reference {
+                kind: VnReferenceKind::Chapter,
+                name: "intro".to_owned()
+            })
+        );
+        assert_eq!(properties.get("short"), Some(&VnValue::Color(0xff00aaff)));
+        assert_eq!(properties.get("opaque"), Some(&VnValue::Color(0xff8000ff)));
+        assert_eq!(
+            properties.get("translucent"),
+            Some(&VnValue::Color(0xff800080))
+        );
+        assert_eq!(
+            properties.get("channels"),
+            Some(&VnValue::Color(0xff800080))
+        );
+        assert_eq!(properties.get("reals"), Some(&VnValue::Color(0xff8000ff)));
+        assert_eq!(properties.get("decade"), Some(&VnValue::Color(0xddeeccff)));
+        assert_eq!(
+            properties.get("palette"),
+            Some(&VnValue::Array(vec![
+                VnValue::Color(0xffffffff),
+                VnValue::Color(0xaabbccff),
+                VnValue::Color(0x000000ff),
+            ]))
+        );
+        assert!(parse("#add\nchapter main {\n    #fff\n    exit\n}\n").is_ok());
+    }
+
+    #[test]
+    fn test_dialogue_shorthand() {
+        let file = parse(
+            r#"
+            config dialogue {
+                action: "vn_dialog.line"
+            }
+
+            chapter intro {
+                rin "Hello!"
+                "It was a sunny day."
+                rin "Ready?" choices: ["Yes" "No"]
+                exit
+                say what: "Bye!"
+            }
+            "#,
+        )
+        .unwrap();
+        let actions = file
+            .story
+            .chapters
+            .get("intro")
+            .unwrap()
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                VnChapterItem::Action(action) => Some(action),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(actions[0].path(), "vn_dialog::line");
+        assert_eq!(
+            actions[0].params.get("who"),
+            Some(&VnValue::Text("rin".to_owned()))
+        );
+        assert_eq!(
+            actions[0].params.get("what"),
+            Some(&VnValue::Text("Hello!".to_owned()))
+        );
+        assert_eq!(actions[0].id.as_deref(), Some("intro.79d87d37"));
+        assert_eq!(actions[1].params.get("who"), None);
+        assert_eq!(actions[1].texts[0].id, "intro.32ad7fa3.what");
+        assert_eq!(actions[2].texts.len(), 3);
+        assert_eq!(actions[3].path(), "exit");
+        assert_eq!(actions[4].path(), "say");
+        assert_eq!(actions[4].id, None);
+    }
+
+    #[test]
+    fn test_text_blocks() {
+        let file = parse(
+            r#"
+            config texts {
+                literal: """
+                    First line.
+                      Indented "quoted" line.
+
+                    Last line.
+                    """
+                folded: >"""
+                    Long monologue
+                    spanning lines.
+
+                    Next paragraph.
+                """
+                inline: """Single "line"."""
+            }
+            "#,
+        )
+        .unwrap();
+        let properties = &file.story.configs.get("texts").unwrap().properties;
+        assert_eq!(
+            properties.get("literal").unwrap().as_text(),
+            Some("First line.\n  Indented \"quoted\" line.\n\nLast line.")
+        );
+        assert_eq!(
+            properties.get("folded").unwrap().as_text(),
+            Some("Long monologue spanning lines.\nNext paragraph.")
+        );
+        assert_eq!(
+            properties.get("inline").unwrap().as_text(),
+            Some("Single \"line\".")
+        );
+    }
+
+    #[test]
+    fn test_imports() {
+        let file = parse(
+            r#"
+            import "chapters/*.vns"
+            import "screens.simp"
+            import optional "extras"
+            import assets "images/"
+            import optional plugin "effects.plugin"
+            import simpleton "ui"
+            "#,
+        )
+        .unwrap();
+        let dependency = |path: &str, kind, optional| VnDependency {
+            path: path.to_owned(),
+            kind,
+            optional,
+        };
+        let expected = [
+            dependency("chapters/*.vns", VnDependencyKind::Script, false),
+            dependency("screens.simp", VnDependencyKind::Simpleton, false),
+            dependency("extras", VnDependencyKind::Script, true),
+            dependency("images/", VnDependencyKind::Assets, false),
+            dependency("effects.plugin", VnDependencyKind::Plugin, true),
+            dependency("ui", VnDependencyKind::Simpleton, false),
+        ];
+        assert_eq!(file.dependencies, expected.into_iter().collect());
+        assert!(file
+            .dependencies
+            .iter()
+            .all(|dependency| dependency.is_glob() == dependency.path.contains('*')));
+    }
 }
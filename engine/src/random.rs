@@ -0,0 +1,100 @@
+use serde::{Deserialize, Serialize};
+
+pub const VN_RANDOM_DEFAULT_SEED: u64 = 0x853C_49E6_748F_EA9B;
+
+/// Deterministic SplitMix64 generator - same seed always produces same sequence,
+/// regardless of platform, so replays and automated tests reproduce exactly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct VnRandom {
+    state: u64,
+}
+
+impl Default for VnRandom {
+    fn default() -> Self {
+        Self::new(VN_RANDOM_DEFAULT_SEED)
+    }
+}
+
+impl VnRandom {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn seed(&mut self, seed: u64) {
+        self.state = seed;
+    }
+
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut result = self.state;
+        result = (result ^ (result >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        result = (result ^ (result >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        result ^ (result >> 31)
+    }
+
+    /// Returns number in `[0; 1)` range.
+    pub fn next_real(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Returns number in `[min; max]` range.
+    pub fn range_integer(&mut self, min: i64, max: i64) -> i64 {
+        let (min, max) = if min <= max { (min, max) } else { (max, min) };
+        let span = max.wrapping_sub(min) as u64;
+        if span == u64::MAX {
+            return self.next_u64() as i64;
+        }
+        min.wrapping_add((self.next_u64() % (span + 1)) as i64)
+    }
+
+    /// Returns number in `[min; max)` range.
+    pub fn range_real(&mut self, min: f64, max: f64) -> f64 {
+        min + (max - min) * self.next_real()
+    }
+
+    /// Returns index of picked weight, or `None` if there is nothing to pick from.
+    pub fn weighted_index(&mut self, weights: &[f64]) -> Option<usize> {
+        let total = weights.iter().map(|weight| weight.max(0.0)).sum::<f64>();
+        if total <= 0.0 {
+            return None;
+        }
+        let mut value = self.range_real(0.0, total);
+        for (index, weight) in weights.iter().enumerate() {
+            let weight = weight.max(0.0);
+            if value < weight {
+                return Some(index);
+            }
+            value -= weight;
+        }
+        weights.iter().rposition(|weight| *weight > 0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_random() {
+        let mut a = VnRandom::new(42);
+        let mut b = VnRandom::new(42);
+        for _ in 0..100 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+        for _ in 0..1000 {
+            let value = a.range_integer(1, 6);
+            assert!((1..=6).contains(&value));
+            let value = a.range_real(-1.0, 1.0);
+            assert!((-1.0..1.0).contains(&value));
+        }
+        assert_eq!(a.weighted_index(&[]), None);
+        assert_eq!(a.weighted_index(&[0.0, 0.0]), None);
+        for _ in 0..100 {
+            assert_eq!(a.weighted_index(&[0.0, 1.0, 0.0]), Some(1));
+        }
+    }
+}
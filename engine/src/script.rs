@@ -1,24 +1,111 @@
-use crate::parser;
+use crate::{action::VnActionParam, parser};
 use intuicio_essentials::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::{
     collections::{HashMap, HashSet},
     error::Error,
+    path::{Path, PathBuf},
 };
 
-#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum VnReferenceKind {
+    Chapter,
+    Character,
+    Scene,
+}
+
+/// Integers and numbers are equal when they represent same value.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub enum VnValue {
     #[default]
     None,
     Boolean(bool),
+    Integer(i64),
     Number(f64),
     Text(String),
+    /// Packed as `0xRRGGBBAA`.
     Color(u32),
+    Vec2 {
+        x: f64,
+        y: f64,
+    },
+    Rect {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+    },
+    /// Story entity, validated with [`VnStory::validate`].
+    Reference {
+        kind: VnReferenceKind,
+        name: String,
+    },
     Array(Vec<VnValue>),
     Map(HashMap<String, VnValue>),
 }
 
+impl PartialEq for VnValue {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::None, Self::None) => true,
+            (Self::Boolean(a), Self::Boolean(b)) => a == b,
+            (Self::Integer(a), Self::Integer(b)) => a == b,
+            (Self::Number(a), Self::Number(b)) => a == b,
+            (Self::Integer(a), Self::Number(b)) | (Self::Number(b), Self::Integer(a)) => {
+                *a as f64 == *b
+            }
+            (Self::Text(a), Self::Text(b)) => a == b,
+            (Self::Color(a), Self::Color(b)) => a == b,
+            (Self::Vec2 { x: ax, y: ay }, Self::Vec2 { x: bx, y: by }) => ax == bx && ay == by,
+            (
+                Self::Rect {
+                    x: ax,
+                    y: ay,
+                    width: aw,
+                    height: ah,
+                },
+                Self::Rect {
+                    x: bx,
+                    y: by,
+                    width: bw,
+                    height: bh,
+                },
+            ) => ax == bx && ay == by && aw == bw && ah == bh,
+            (Self::Reference { kind: ak, name: an }, Self::Reference { kind: bk, name: bn }) => {
+                ak == bk && an == bn
+            }
+            (Self::Array(a), Self::Array(b)) => a == b,
+            (Self::Map(a), Self::Map(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
 impl VnValue {
+    pub fn rgba(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self::Color(u32::from_be_bytes([r, g, b, a]))
+    }
+
+    /// Color from `RGB`, `RRGGBB` or `RRGGBBAA` hex digits, with optional `#` prefix.
+    pub fn hex(digits: &str) -> Option<Self> {
+        let digits = digits.strip_prefix('#').unwrap_or(digits);
+        if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        let value = u32::from_str_radix(digits, 16).ok()?;
+        let value = match digits.len() {
+            3 => {
+                let [_, _, high, low] = value.to_be_bytes();
+                let (r, g, b) = (high & 0xf, low >> 4, low & 0xf);
+                u32::from_be_bytes([r * 0x11, g * 0x11, b * 0x11, 0xff])
+            }
+            6 => (value << 8) | 0xff,
+            8 => value,
+            _ => return None,
+        };
+        Some(Self::Color(value))
+    }
+
     pub fn is_none(&self) -> bool {
         matches!(self, Self::None)
     }
@@ -31,11 +118,21 @@ impl VnValue {
         }
     }
 
+    /// Numbers without fractional part are accepted too.
+    pub fn as_integer(&self) -> Option<i64> {
+        match self {
+            Self::Integer(value) => Some(*value),
+            Self::Number(value) if value.fract() == 0.0 => Some(*value as i64),
+            _ => None,
+        }
+    }
+
+    /// Integers are accepted too.
     pub fn as_number(&self) -> Option<f64> {
-        if let Self::Number(value) = self {
-            Some(*value)
-        } else {
-            None
+        match self {
+            Self::Number(value) => Some(*value),
+            Self::Integer(value) => Some(*value as f64),
+            _ => None,
         }
     }
 
@@ -55,6 +152,53 @@ impl VnValue {
         }
     }
 
+    /// Red, green, blue and alpha channels.
+    pub fn as_rgba(&self) -> Option<(u8, u8, u8, u8)> {
+        let [r, g, b, a] = self.as_color()?.to_be_bytes();
+        Some((r, g, b, a))
+    }
+
+    /// Red, green, blue and alpha channels in 0-1 range.
+    pub fn as_rgba_normalized(&self) -> Option<(f64, f64, f64, f64)> {
+        let (r, g, b, a) = self.as_rgba()?;
+        Some((
+            r as f64 / 255.0,
+            g as f64 / 255.0,
+            b as f64 / 255.0,
+            a as f64 / 255.0,
+        ))
+    }
+
+    pub fn as_vec2(&self) -> Option<(f64, f64)> {
+        if let Self::Vec2 { x, y } = self {
+            Some((*x, *y))
+        } else {
+            None
+        }
+    }
+
+    pub fn as_rect(&self) -> Option<(f64, f64, f64, f64)> {
+        if let Self::Rect {
+            x,
+            y,
+            width,
+            height,
+        } = self
+        {
+            Some((*x, *y, *width, *height))
+        } else {
+            None
+        }
+    }
+
+    pub fn as_reference(&self) -> Option<(VnReferenceKind, &str)> {
+        if let Self::Reference { kind, name } = self {
+            Some((*kind, name))
+        } else {
+            None
+        }
+    }
+
     pub fn as_array(&self) -> Option<&[Self]> {
         if let Self::Array(value) = self {
             Some(value)
@@ -71,24 +215,58 @@ impl VnValue {
         }
     }
 
+    /// Integers and numbers are considered same type.
     pub fn is_same_type(&self, other: &Self) -> bool {
         matches!(
             (self, other),
             (Self::None, Self::None)
                 | (Self::Boolean(_), Self::Boolean(_))
-                | (Self::Number(_), Self::Number(_))
+                | (
+                    Self::Integer(_) | Self::Number(_),
+                    Self::Integer(_) | Self::Number(_)
+                )
                 | (Self::Text(_), Self::Text(_))
                 | (Self::Color(_), Self::Color(_))
+                | (Self::Vec2 { .. }, Self::Vec2 { .. })
+                | (Self::Rect { .. }, Self::Rect { .. })
+                | (Self::Reference { .. }, Self::Reference { .. })
                 | (Self::Array(_), Self::Array(_))
                 | (Self::Map(_), Self::Map(_))
         )
     }
+
+    /// Calls visitor for this value and all values nested in it.
+    pub fn visit(&self, visitor: &mut impl FnMut(&Self)) {
+        visitor(self);
+        match self {
+            Self::Array(items) => {
+                for item in items {
+                    item.visit(visitor);
+                }
+            }
+            Self::Map(items) => {
+                for item in items.values() {
+                    item.visit(visitor);
+                }
+            }
+            _ => {}
+        }
+    }
 }
 
-#[derive(Default, Clone)]
+/// Identifies suspended action that host has to resolve to let VM continue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct VnToken(pub u64);
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum VnResult {
     #[default]
     Continue,
+    /// Evaluate same action again in next step.
+    Wait,
+    /// Continue after this action once token gets resolved.
+    Suspend(VnToken),
     JumpTo {
         chapter: Option<String>,
         label: Option<String>,
@@ -98,11 +276,76 @@ pub enum VnResult {
         label: Option<String>,
     },
     Exit,
+    /// Start chapter in separate thread, replacing existing thread of that name.
+    Spawn {
+        thread: String,
+        chapter: Option<String>,
+        label: Option<String>,
+    },
+    Kill {
+        thread: String,
+    },
+    /// Continue after this action once given thread completes.
+    Join {
+        thread: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum VnDependencyKind {
+    Script,
+    Plugin,
+    Simpleton,
+    /// Folder of assets.
+    Assets,
+}
+
+impl VnDependencyKind {
+    /// Kind of untyped import: folders with trailing `/` are assets,
+    /// `plugin`, `simp` and `bimp` extensions are plugins and Simpleton modules, rest are scripts.
+    pub fn of_path(path: &str) -> Self {
+        if path.ends_with('/') {
+            return Self::Assets;
+        }
+        match Path::new(path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+        {
+            Some("plugin") => Self::Plugin,
+            Some("simp") | Some("bimp") => Self::Simpleton,
+            _ => Self::Script,
+        }
+    }
+
+    /// Extensions tried in order for imports of this kind written without one.
+    pub fn extensions(&self) -> &'static [&'static str] {
+        match self {
+            Self::Script => &["vns"],
+            Self::Plugin => &["plugin"],
+            Self::Simpleton => &["simp", "bimp"],
+            Self::Assets => &[],
+        }
+    }
+}
+
+/// Imported file or folder, path can contain `*` and `?` wildcards matching within single segment.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct VnDependency {
+    pub path: String,
+    pub kind: VnDependencyKind,
+    /// Missing optional dependency gets skipped instead of failing package load.
+    pub optional: bool,
+}
+
+impl VnDependency {
+    pub fn is_glob(&self) -> bool {
+        self.path.contains(['*', '?'])
+    }
 }
 
 #[derive(Debug, Default)]
 pub struct VnFile {
-    pub dependencies: HashSet<String>,
+    pub dependencies: HashSet<VnDependency>,
     pub story: VnStory,
 }
 
@@ -120,6 +363,256 @@ pub struct VnStory {
     pub chapters: HashMap<String, VnChapter>,
 }
 
+impl VnStory {
+    /// Config with `action` property naming action that shorthand dialogue lines
+    /// compile into, like `action: "vn_dialog.say"`. Defaults to [`VnAction::DIALOGUE`].
+    pub const DIALOGUE_CONFIG: &'static str = "dialogue";
+
+    /// Name and module name of action configured in [`Self::DIALOGUE_CONFIG`].
+    pub fn dialogue_action(&self) -> (String, Option<String>) {
+        let path = self
+            .configs
+            .get(Self::DIALOGUE_CONFIG)
+            .and_then(|config| config.properties.get("action"))
+            .and_then(|action| action.as_text())
+            .unwrap_or(VnAction::DIALOGUE);
+        match path.split_once('.') {
+            Some((module_name, name)) => (name.to_owned(), Some(module_name.to_owned())),
+            None => (path.to_owned(), None),
+        }
+    }
+
+    /// Points shorthand dialogue lines at action configured in [`Self::DIALOGUE_CONFIG`],
+    /// assigns line ids to all calls of that action and text ids to text literals of all actions.
+    pub fn resolve_shorthands(&mut self) {
+        let (name, module_name) = self.dialogue_action();
+        for chapter in self.chapters.values_mut() {
+            for item in &mut chapter.items {
+                if let VnChapterItem::Action(action) = item {
+                    if action.shorthand {
+                        action.name = name.to_owned();
+                        action.module_name = module_name.to_owned();
+                    }
+                }
+            }
+        }
+        self.assign_line_ids(&name, module_name.as_deref());
+    }
+
+    /// Dialogue line ids are `chapter.label.hash`, where hash comes from speaker and
+    /// texts of the line, so adding or removing other lines does not change them.
+    /// Repeated lines within the same label get their occurrence appended, like `hash-2`.
+    /// Other actions with text literals get keys made the same way from their path,
+    /// used only as prefix of their text ids.
+    fn assign_line_ids(&mut self, name: &str, module_name: Option<&str>) {
+        for (chapter_name, chapter) in &mut self.chapters {
+            let mut label = None;
+            let mut occurrences = HashMap::<String, usize>::new();
+            for item in &mut chapter.items {
+                let action = match item {
+                    VnChapterItem::Label(name) => {
+                        label = Some(name.to_owned());
+                        occurrences.clear();
+                        continue;
+                    }
+                    VnChapterItem::Action(action) => action,
+                };
+                action.id = None;
+                action.texts = vec![];
+                let dialogue = action.name == name && action.module_name.as_deref() == module_name;
+                if !dialogue && action.literals.is_empty() {
+                    continue;
+                }
+                let mut content = if dialogue {
+                    action
+                        .literal(VnAction::WHO_PARAM, None)
+                        .unwrap_or_default()
+                        .to_owned()
+                } else {
+                    action.path()
+                };
+                for (param, index) in &action.literals {
+                    content.push('\n');
+                    content.push_str(action.literal(param, *index).unwrap_or_default());
+                }
+                let key = match label.as_ref() {
+                    Some(label) => format!("{}.{}.{:08x}", chapter_name, label, hash(&content)),
+                    None => format!("{}.{:08x}", chapter_name, hash(&content)),
+                };
+                let occurrence = occurrences.entry(key.to_owned()).or_default();
+                *occurrence += 1;
+                let key = match *occurrence {
+                    1 => key,
+                    occurrence => format!("{}-{}", key, occurrence),
+                };
+                if dialogue {
+                    action.id = Some(key.to_owned());
+                }
+                let line_id = action.line_id().unwrap_or(&key);
+                action.texts = action
+                    .literals
+                    .iter()
+                    .map(|(param, index)| VnTextId {
+                        id: VnTextId::format(line_id, param, *index),
+                        param: param.to_owned(),
+                        index: *index,
+                    })
+                    .collect();
+            }
+        }
+    }
+
+    /// Checks that all references point to existing story entities.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = vec![];
+        let mut check = |location: String, value: &VnValue| {
+            value.visit(&mut |value| {
+                if let Some((kind, name)) = value.as_reference() {
+                    let exists = match kind {
+                        VnReferenceKind::Chapter => self.chapters.contains_key(name),
+                        VnReferenceKind::Character => self.characters.contains_key(name),
+                        VnReferenceKind::Scene => self.scenes.contains_key(name),
+                    };
+                    if !exists {
+                        errors.push(format!("{}: unknown {:?} `{}`", location, kind, name));
+                    }
+                }
+            });
+        };
+        for (name, config) in &self.configs {
+            for (key, value) in &config.properties {
+                check(format!("config {}.{}", name, key), value);
+            }
+        }
+        for (name, character) in &self.characters {
+            for (key, value) in &character.properties {
+                check(format!("character {}.{}", name, key), value);
+            }
+        }
+        for (name, scene) in &self.scenes {
+            for (key, value) in &scene.properties {
+                check(format!("scene {}.{}", name, key), value);
+            }
+        }
+        for (name, chapter) in &self.chapters {
+            for (position, item) in chapter.items.iter().enumerate() {
+                if let VnChapterItem::Action(action) = item {
+                    let location = chapter.location(name, position);
+                    for (key, value) in &action.params {
+                        check(format!("{} {}", location, key), value);
+                    }
+                }
+            }
+        }
+        // Threads spawned into chapters, joining them from there would never complete.
+        let mut spawned = HashSet::new();
+        for (name, chapter) in &self.chapters {
+            for item in &chapter.items {
+                if let VnChapterItem::Action(action) = item {
+                    if !action.is_builtin("spawn") {
+                        continue;
+                    }
+                    if let Some(thread) = action.name_param("thread") {
+                        let chapter = action.name_param("chapter").unwrap_or(name);
+                        spawned.insert((chapter.to_owned(), thread.to_owned()));
+                    }
+                }
+            }
+        }
+        for (name, chapter) in &self.chapters {
+            for (position, item) in chapter.items.iter().enumerate() {
+                if let VnChapterItem::Action(action) = item {
+                    let Some(thread) = action.name_param("thread") else {
+                        continue;
+                    };
+                    if action.is_builtin("join")
+                        && spawned.contains(&(name.to_owned(), thread.to_owned()))
+                    {
+                        errors.push(format!(
+                            "{} thread: joins its own `{}` thread",
+                            chapter.location(name, position),
+                            thread
+                        ));
+                    }
+                }
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            errors.sort();
+            Err(errors)
+        }
+    }
+
+    /// Checks that all actions exist in registry, get their required params
+    /// and that params match declared types.
+    pub fn validate_actions(&self, registry: &Registry) -> Result<(), Vec<String>> {
+        let mut errors = vec![];
+        for (name, chapter) in &self.chapters {
+            for (position, item) in chapter.items.iter().enumerate() {
+                if let VnChapterItem::Action(action) = item {
+                    let location = chapter.location(name, position);
+                    let Some(function) = action.find_function(registry) else {
+                        errors.push(format!("{}: unknown action `{}`", location, action.path()));
+                        continue;
+                    };
+                    let inputs = &function.signature().inputs;
+                    let mut unknown = action
+                        .params
+                        .keys()
+                        .filter(|name| !inputs.iter().any(|input| &input.name == *name))
+                        .collect::<Vec<_>>();
+                    unknown.sort();
+                    for name in unknown {
+                        errors.push(format!("{} {}: unknown param", location, name));
+                    }
+                    for param in inputs.iter() {
+                        let param = VnActionParam::of_param(param);
+                        let mut value = action.param(&param.name);
+                        if value.is_none() {
+                            if param.required {
+                                errors.push(format!(
+                                    "{} {}: missing required param",
+                                    location, param.name
+                                ));
+                                continue;
+                            }
+                            value = param.default.unwrap_or_default();
+                        }
+                        if let Some(param_type) = param.param_type {
+                            if !param_type.accepts(&value) {
+                                errors.push(format!(
+                                    "{} {}: expected {}",
+                                    location, param.name, param_type
+                                ));
+                            }
+                        }
+                    }
+                    if action.is_builtin("jump_random") {
+                        let length = |name| action.param(name).as_array().map(|items| items.len());
+                        if let (Some(labels), Some(weights)) = (length("labels"), length("weights"))
+                        {
+                            if labels != weights {
+                                errors.push(format!(
+                                    "{} weights: expected {} items, one per label",
+                                    location, labels
+                                ));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            errors.sort();
+            Err(errors)
+        }
+    }
+}
+
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct VnConfig {
     pub properties: HashMap<String, VnValue>,
@@ -138,6 +631,75 @@ pub struct VnScene {
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct VnChapter {
     pub items: Vec<VnChapterItem>,
+    /// Events that make VM enter this chapter as their handler.
+    #[serde(default)]
+    pub triggers: Vec<VnTrigger>,
+    /// Source file of this chapter, if known.
+    #[serde(default)]
+    pub source: Option<String>,
+    /// Source line of each item, matching items order.
+    #[serde(default)]
+    pub lines: Vec<usize>,
+}
+
+impl VnChapter {
+    pub fn line(&self, position: usize) -> Option<usize> {
+        self.lines.get(position).copied()
+    }
+
+    /// Source file and line of item if known, its position in chapter otherwise.
+    pub fn location(&self, name: &str, position: usize) -> String {
+        match (self.source.as_ref(), self.line(position)) {
+            (Some(source), Some(line)) => format!("{}:{}", source, line),
+            _ => format!("chapter {} item {}", name, position),
+        }
+    }
+
+    pub fn find_label(&self, label: &str) -> Option<usize> {
+        self.items.iter().position(|item| {
+            if let VnChapterItem::Label(name) = item {
+                name == label
+            } else {
+                false
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VnTrigger {
+    pub event: String,
+    pub argument: Option<String>,
+}
+
+impl VnTrigger {
+    pub fn matches(&self, event: &VnEvent) -> bool {
+        self.event == event.name
+            && self
+                .argument
+                .as_ref()
+                .map(|argument| Some(argument) == event.argument.as_ref())
+                .unwrap_or(true)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VnEvent {
+    pub name: String,
+    pub argument: Option<String>,
+}
+
+impl VnEvent {
+    pub const CHAPTER_ENTER: &'static str = "chapter_enter";
+    pub const CHAPTER_EXIT: &'static str = "chapter_exit";
+    pub const GLOBAL_CHANGED: &'static str = "global_changed";
+
+    pub fn new(name: impl ToString, argument: Option<impl ToString>) -> Self {
+        Self {
+            name: name.to_string(),
+            argument: argument.map(|argument| argument.to_string()),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -151,23 +713,132 @@ pub struct VnAction {
     pub name: String,
     pub module_name: Option<String>,
     pub params: HashMap<String, VnValue>,
+    /// Stable id of dialogue line, assigned by [`VnStory::resolve_shorthands`].
+    #[serde(default)]
+    pub id: Option<String>,
+    /// Localizable text literals of params of dialogue line.
+    #[serde(default)]
+    pub texts: Vec<VnTextId>,
+    /// Params written as text literals, with item index for array params.
+    #[serde(default)]
+    pub literals: Vec<(String, Option<usize>)>,
+    /// Written as `who "what"` line, which compiles into configured dialogue action.
+    #[serde(default)]
+    pub shorthand: bool,
+}
+
+/// Stable id of text literal in action param, `line_id.param` or `line_id.param.index`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VnTextId {
+    pub id: String,
+    pub param: String,
+    /// Index of item for array params.
+    pub index: Option<usize>,
+}
+
+impl VnTextId {
+    pub fn format(line_id: &str, param: &str, index: Option<usize>) -> String {
+        match index {
+            Some(index) => format!("{}.{}.{}", line_id, param, index),
+            None => format!("{}.{}", line_id, param),
+        }
+    }
 }
 
 impl VnAction {
-    pub fn evaluate(&self, context: &mut Context, registry: &Registry) -> VnResult {
-        let function = registry
-            .find_function(FunctionQuery {
-                name: Some(self.name.as_str().into()),
-                module_name: self.module_name.as_ref().map(|name| name.into()),
-                ..Default::default()
+    /// Action that shorthand dialogue lines compile into, unless configured otherwise.
+    pub const DIALOGUE: &'static str = "say";
+    /// Param that overrides generated id and gets it passed into function.
+    pub const ID_PARAM: &'static str = "id";
+    /// Params that shorthand dialogue lines fill with speaking character and text.
+    pub const WHO_PARAM: &'static str = "who";
+    pub const WHAT_PARAM: &'static str = "what";
+    /// Param of dialogue lines with texts of choices player picks from.
+    pub const CHOICES_PARAM: &'static str = "choices";
+
+    /// Explicit `id` param if present, otherwise generated id.
+    pub fn line_id(&self) -> Option<&str> {
+        self.params
+            .get(Self::ID_PARAM)
+            .and_then(|id| id.as_text())
+            .or(self.id.as_deref())
+    }
+
+    /// Value passed into function param of given name, `none` if missing.
+    /// Text of param, or of its item for array params.
+    pub fn literal(&self, name: &str, index: Option<usize>) -> Option<&str> {
+        let value = self.params.get(name)?;
+        match index {
+            Some(index) => value.as_array()?.get(index)?.as_text(),
+            None => value.as_text(),
+        }
+    }
+
+    pub fn param(&self, name: &str) -> VnValue {
+        if let Some(value) = self.params.get(name) {
+            value.clone()
+        } else if let Some(id) = self.id.as_ref().filter(|_| name == Self::ID_PARAM) {
+            VnValue::Text(id.to_owned())
+        } else {
+            VnValue::None
+        }
+    }
+
+    /// Params as function receives them, with missing ones replaced by their defaults.
+    pub fn resolved_params(&self, registry: &Registry) -> HashMap<String, VnValue> {
+        let Some(function) = self.find_function(registry) else {
+            return self.params.to_owned();
+        };
+        function
+            .signature()
+            .inputs
+            .iter()
+            .filter_map(|param| {
+                let value = match self.param(&param.name) {
+                    VnValue::None => VnActionParam::of_param(param).default?,
+                    value => value,
+                };
+                Some((param.name.to_owned(), value))
             })
-            .unwrap_or_else(|| {
-                panic!(
-                    "Function {}::{} not found in registry!",
-                    self.module_name.as_deref().unwrap_or(""),
-                    self.name
-                )
-            });
+            .collect()
+    }
+
+    /// Tells if it is given action of `vn` module, written with or without module name.
+    pub fn is_builtin(&self, name: &str) -> bool {
+        self.name == name && self.module_name.as_deref().unwrap_or("vn") == "vn"
+    }
+
+    /// Text or reference name in given param.
+    pub fn name_param(&self, name: &str) -> Option<&str> {
+        let value = self.params.get(name)?;
+        value
+            .as_text()
+            .or_else(|| value.as_reference().map(|(_, name)| name))
+    }
+
+    pub fn path(&self) -> String {
+        match self.module_name.as_ref() {
+            Some(module_name) => format!("{}::{}", module_name, self.name),
+            None => self.name.to_owned(),
+        }
+    }
+
+    pub fn find_function(&self, registry: &Registry) -> Option<FunctionHandle> {
+        registry.find_function(FunctionQuery {
+            name: Some(self.name.as_str().into()),
+            module_name: self.module_name.as_ref().map(|name| name.into()),
+            ..Default::default()
+        })
+    }
+
+    pub fn evaluate(&self, context: &mut Context, registry: &Registry) -> VnResult {
+        let function = self.find_function(registry).unwrap_or_else(|| {
+            panic!(
+                "Function {}::{} not found in registry!",
+                self.module_name.as_deref().unwrap_or(""),
+                self.name
+            )
+        });
         let value_type = TypeHash::of::<VnValue>();
         if function.signature().outputs.len() == 1 {
             let param = &function.signature().outputs[0];
@@ -195,11 +866,8 @@ impl VnAction {
                     param.name
                 );
             }
-            if let Some(value) = self.params.get(&param.name) {
-                context.stack().push(value.clone());
-            } else {
-                context.stack().push(VnValue::None);
-            }
+            // Missing params get their defaults from action function itself.
+            context.stack().push(self.param(&param.name));
         }
         function.invoke(context, registry);
         context.stack().pop::<VnResult>().unwrap()
@@ -209,6 +877,10 @@ impl VnAction {
 #[derive(Debug, Default)]
 pub struct VnPackage {
     pub files: HashMap<String, VnFile>,
+    /// Dependencies of all files, with paths resolved against importing file and globs expanded.
+    /// Plugin paths are kept as imported, to be looked up in search paths, and script globs are
+    /// kept as patterns expanded by content provider.
+    pub dependencies: Vec<VnDependency>,
 }
 
 impl VnPackage {
@@ -230,18 +902,96 @@ impl VnPackage {
             return Ok(());
         }
         for content in content_provider.unpack_load(&path)? {
-            if let Some(module) = content.data? {
-                let dependencies = module.dependencies.to_owned();
+            // Globs can match files that are already loaded.
+            if self.files.contains_key(&content.name) {
+                continue;
+            }
+            if let Some(mut module) = content.data? {
+                for chapter in module.story.chapters.values_mut() {
+                    chapter.source = Some(content.name.to_owned());
+                }
+                let mut dependencies = module.dependencies.iter().cloned().collect::<Vec<_>>();
+                dependencies.sort_by(|a, b| a.path.cmp(&b.path));
                 self.files.insert(content.name, module);
-                for relative in dependencies {
-                    let path = content_provider.join_paths(&content.path, &relative)?;
-                    self.load(&path, content_provider)?;
+                for dependency in dependencies {
+                    self.load_dependency(&content.path, dependency, content_provider)?;
                 }
             }
         }
         Ok(())
     }
 
+    /// Dependencies of given kind.
+    pub fn dependencies_of(&self, kind: VnDependencyKind) -> impl Iterator<Item = &VnDependency> {
+        self.dependencies
+            .iter()
+            .filter(move |dependency| dependency.kind == kind)
+    }
+
+    fn load_dependency<CP>(
+        &mut self,
+        parent: &str,
+        dependency: VnDependency,
+        content_provider: &mut CP,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        CP: ScriptContentProvider<VnFile>,
+    {
+        match dependency.kind {
+            VnDependencyKind::Plugin => {
+                self.add_dependency(dependency);
+                return Ok(());
+            }
+            // Content provider decides what script paths and globs point at.
+            VnDependencyKind::Script => {
+                let path = content_provider.join_paths(parent, &dependency.path)?;
+                if !dependency.is_glob() && content_provider.sanitize_path(&path).is_err() {
+                    if dependency.optional {
+                        return Ok(());
+                    }
+                    return Err(format!("Could not find `{}` dependency!", path).into());
+                }
+                self.load(&path, content_provider)?;
+                self.add_dependency(VnDependency { path, ..dependency });
+                return Ok(());
+            }
+            VnDependencyKind::Simpleton | VnDependencyKind::Assets => {}
+        }
+        let paths = if dependency.is_glob() {
+            glob_paths(&join_directory(parent, &dependency.path))
+        } else {
+            vec![join_directory(parent, &dependency.path)]
+        };
+        for path in paths {
+            let candidates = if Path::new(&path).extension().is_none() {
+                dependency
+                    .kind
+                    .extensions()
+                    .iter()
+                    .map(|extension| format!("{}.{}", path, extension))
+                    .chain(std::iter::once(path.to_owned()))
+                    .collect()
+            } else {
+                vec![path.to_owned()]
+            };
+            match candidates.into_iter().find(|path| Path::new(path).exists()) {
+                Some(path) => self.add_dependency(VnDependency {
+                    path,
+                    ..dependency.to_owned()
+                }),
+                None if dependency.optional => {}
+                None => return Err(format!("Could not find `{}` dependency!", path).into()),
+            }
+        }
+        Ok(())
+    }
+
+    fn add_dependency(&mut self, dependency: VnDependency) {
+        if !self.dependencies.contains(&dependency) {
+            self.dependencies.push(dependency);
+        }
+    }
+
     pub fn compile(self) -> VnStory {
         let mut result = VnStory::default();
         for file in self.files.into_values() {
@@ -250,44 +1000,154 @@ impl VnPackage {
             result.scenes.extend(file.story.scenes);
             result.chapters.extend(file.story.chapters);
         }
+        result.resolve_shorthands();
         result
     }
 
     #[cfg(feature = "plugins")]
     pub fn install_plugins(&self, registry: &mut Registry, search_paths: &[&str]) {
         use intuicio_essentials::{core::core_version, prelude::*};
-        use std::{env::consts::DLL_EXTENSION, path::PathBuf};
-
-        for file in self.files.values() {
-            'plugin: for path in &file.dependencies {
-                let mut path = PathBuf::from(path);
-                if path
-                    .extension()
-                    .map(|extension| extension == "plugin")
-                    .unwrap_or_default()
+        use std::env::consts::DLL_EXTENSION;
+
+        'plugin: for dependency in self.dependencies_of(VnDependencyKind::Plugin) {
+            let mut path = PathBuf::from(&dependency.path);
+            path.set_extension(DLL_EXTENSION);
+            for search_path in search_paths {
+                let path = PathBuf::from(search_path).join(&path);
+                if install_plugin(
+                    path.to_string_lossy().as_ref(),
+                    registry,
+                    Some(core_version()),
+                )
+                .is_ok()
                 {
-                    path.set_extension(DLL_EXTENSION);
-                    for search_path in search_paths {
-                        let path = PathBuf::from(search_path).join(&path);
-                        if install_plugin(
-                            path.to_string_lossy().as_ref(),
-                            registry,
-                            Some(core_version()),
-                        )
-                        .is_ok()
-                        {
-                            continue 'plugin;
-                        }
-                    }
-                    panic!("Could not load plugin: {:?}", path);
+                    continue 'plugin;
                 }
             }
+            if !dependency.optional {
+                panic!("Could not load plugin: {:?}", path);
+            }
+        }
+    }
+}
+
+/// FNV-1a, stable across platforms and compiler versions unlike std hashers.
+fn hash(content: &str) -> u32 {
+    content.bytes().fold(0x811c9dc5, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(0x01000193)
+    })
+}
+
+/// Path relative to directory of parent file.
+fn join_directory(parent: &str, relative: &str) -> String {
+    let mut path = PathBuf::from(parent);
+    path.pop();
+    path.join(relative).to_string_lossy().into_owned()
+}
+
+/// Existing paths matching pattern with `*` and `?` wildcards, sorted.
+fn glob_paths(pattern: &str) -> Vec<String> {
+    let mut result = vec![PathBuf::new()];
+    for component in Path::new(pattern).components() {
+        let segment = component.as_os_str().to_string_lossy();
+        if !segment.contains(['*', '?']) {
+            for path in &mut result {
+                path.push(component);
+            }
+            continue;
+        }
+        let segment = segment.chars().collect::<Vec<_>>();
+        result = result
+            .into_iter()
+            .filter_map(|directory| {
+                let directory = if directory.as_os_str().is_empty() {
+                    PathBuf::from(".")
+                } else {
+                    directory
+                };
+                std::fs::read_dir(&directory).ok()
+            })
+            .flatten()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| {
+                let name = entry
+                    .file_name()
+                    .to_string_lossy()
+                    .chars()
+                    .collect::<Vec<_>>();
+                // Hidden files match only explicitly.
+                (name.first() != Some(&'.') || segment.first() == Some(&'.'))
+                    && matches_wildcard(&segment, &name)
+            })
+            .map(|entry| entry.path())
+            .collect();
+    }
+    let mut result = result
+        .into_iter()
+        .filter(|path| path.exists())
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect::<Vec<_>>();
+    result.sort();
+    result
+}
+
+fn matches_wildcard(pattern: &[char], text: &[char]) -> bool {
+    match (pattern.first(), text.first()) {
+        (None, None) => true,
+        (Some('*'), _) => {
+            matches_wildcard(&pattern[1..], text)
+                || (!text.is_empty() && matches_wildcard(pattern, &text[1..]))
         }
+        (Some('?'), Some(_)) => matches_wildcard(&pattern[1..], &text[1..]),
+        (Some(a), Some(b)) if a == b => matches_wildcard(&pattern[1..], &text[1..]),
+        _ => false,
     }
 }
 
 pub struct VnContentParser;
 
+/// Content provider that expands `*` and `?` wildcards of paths into all matching
+/// files, loaded with inner content provider.
+pub struct VnGlobContentProvider<CP> {
+    inner: CP,
+}
+
+impl<CP> VnGlobContentProvider<CP> {
+    pub fn new(inner: CP) -> Self {
+        Self { inner }
+    }
+}
+
+impl<T, CP: ScriptContentProvider<T>> ScriptContentProvider<T> for VnGlobContentProvider<CP> {
+    fn load(&mut self, path: &str) -> Result<Option<T>, Box<dyn Error>> {
+        self.inner.load(path)
+    }
+
+    fn unpack_load(&mut self, path: &str) -> Result<Vec<ScriptContent<T>>, Box<dyn Error>> {
+        if !path.contains(['*', '?']) {
+            return self.inner.unpack_load(path);
+        }
+        let mut result = vec![];
+        for path in glob_paths(path) {
+            let path = self.inner.sanitize_path(&path)?;
+            result.extend(self.inner.unpack_load(&path)?);
+        }
+        Ok(result)
+    }
+
+    fn sanitize_path(&self, path: &str) -> Result<String, Box<dyn Error>> {
+        if path.contains(['*', '?']) {
+            Ok(path.to_owned())
+        } else {
+            self.inner.sanitize_path(path)
+        }
+    }
+
+    fn join_paths(&self, parent: &str, relative: &str) -> Result<String, Box<dyn Error>> {
+        self.inner.join_paths(parent, relative)
+    }
+}
+
 impl BytesContentParser<VnFile> for VnContentParser {
     fn parse(&self, bytes: Vec<u8>) -> Result<VnFile, Box<dyn Error>> {
         let content = String::from_utf8(bytes)?;
@@ -302,12 +1162,97 @@ mod tests {
     #[test]
     fn test_script() {
         let mut content_provider = ExtensionContentProvider::<VnFile>::default()
-            .extension("vns", FileContentProvider::new("vns", VnContentParser))
-            .extension("plugin", IgnoreContentProvider)
-            .extension("simp", IgnoreContentProvider)
+            .extension(
+                "vns",
+                VnGlobContentProvider::new(FileContentProvider::new("vns", VnContentParser)),
+            )
             .default_extension("vns");
-        VnPackage::new("../resources/main.vns", &mut content_provider)
-            .unwrap()
-            .compile();
+        let package = VnPackage::new("../resources/main.vns", &mut content_provider).unwrap();
+        assert_eq!(
+            package.dependencies_of(VnDependencyKind::Simpleton).count(),
+            1
+        );
+        assert_eq!(package.dependencies_of(VnDependencyKind::Assets).count(), 2);
+        package.compile().validate().unwrap();
+
+        let package = VnPackage::new("../resources/*.vns", &mut content_provider).unwrap();
+        assert_eq!(package.files.len(), 1);
+
+        let mut package = VnPackage::default();
+        package
+            .load_dependency(
+                "../resources/main.vns",
+                VnDependency {
+                    path: "screens".to_owned(),
+                    kind: VnDependencyKind::Simpleton,
+                    optional: false,
+                },
+                &mut content_provider,
+            )
+            .unwrap();
+        assert!(package.dependencies[0].path.ends_with("screens.simp"));
+    }
+
+    #[test]
+    fn test_wildcards() {
+        let matches = |pattern: &str, text: &str| {
+            matches_wildcard(
+                &pattern.chars().collect::<Vec<_>>(),
+                &text.chars().collect::<Vec<_>>(),
+            )
+        };
+        assert!(matches("*.vns", "intro.vns"));
+        assert!(matches("ch??.vns", "ch01.vns"));
+        assert!(matches("*", ""));
+        assert!(!matches("*.vns", "intro.simp"));
+        assert!(!matches("ch?.vns", "ch01.vns"));
+        let mut paths = glob_paths("../resources/*/*.ttf");
+        assert_eq!(paths.len(), 1);
+        assert!(paths.pop().unwrap().ends_with("Roboto-Regular.ttf"));
+    }
+
+    #[test]
+    fn test_values() {
+        assert_eq!(VnValue::Integer(1), VnValue::Number(1.0));
+        assert_ne!(VnValue::Integer(1), VnValue::Number(1.5));
+        assert_eq!(
+            VnValue::Array(vec![VnValue::Number(2.0)]),
+            VnValue::Array(vec![VnValue::Integer(2)])
+        );
+        assert_eq!(VnValue::Number(3.0).as_integer(), Some(3));
+        assert_eq!(VnValue::Number(3.5).as_integer(), None);
+        assert!(VnValue::Integer(0).is_same_type(&VnValue::Number(0.5)));
+        assert_eq!(VnValue::rgba(255, 0, 51, 255), VnValue::Color(0xff0033ff));
+        assert_eq!(VnValue::hex("#f03"), Some(VnValue::Color(0xff0033ff)));
+        assert_eq!(VnValue::hex("11223344"), Some(VnValue::Color(0x11223344)));
+        assert_eq!(VnValue::hex("#ff00"), None);
+        assert_eq!(
+            VnValue::Color(0x11223344).as_rgba(),
+            Some((0x11, 0x22, 0x33, 0x44))
+        );
+        assert_eq!(
+            VnValue::Color(0xff0000ff).as_rgba_normalized(),
+            Some((1.0, 0.0, 0.0, 1.0))
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let story = VnFile::parse(
+            r#"
+            chapter main {
+                jump_to target: chapter(main)
+                show character: character(rin)
+            }
+            "#,
+        )
+        .unwrap()
+        .story;
+        assert_eq!(
+            story.validate(),
+            Err(vec![
+                "chapter main item 1 character: unknown Character `rin`".to_owned()
+            ])
+        );
     }
 }
@@ -0,0 +1,307 @@
+use crate::script::{VnChapterItem, VnResult, VnStory, VnValue};
+use serde::{Deserialize, Serialize};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap, HashSet},
+    error::Error,
+    fmt::{Display, Formatter},
+    io::{BufRead, Write},
+    rc::Rc,
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VnTraceRecord {
+    pub thread: String,
+    pub chapter: String,
+    pub position: usize,
+    #[serde(flatten)]
+    pub item: VnTraceItem,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum VnTraceItem {
+    Label {
+        name: String,
+    },
+    Action {
+        function: String,
+        params: HashMap<String, VnValue>,
+        result: VnResult,
+    },
+}
+
+/// Sink of records of everything that VM executes.
+pub trait VnTracer {
+    fn trace(&mut self, record: &VnTraceRecord);
+}
+
+impl<T: VnTracer> VnTracer for Rc<RefCell<T>> {
+    fn trace(&mut self, record: &VnTraceRecord) {
+        self.borrow_mut().trace(record);
+    }
+}
+
+/// Keeps records in memory.
+#[derive(Debug, Default)]
+pub struct VnTraceRecorder {
+    pub records: Vec<VnTraceRecord>,
+}
+
+impl VnTracer for VnTraceRecorder {
+    fn trace(&mut self, record: &VnTraceRecord) {
+        self.records.push(record.clone());
+    }
+}
+
+/// Writes records as JSON lines.
+pub struct VnTraceWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> VnTraceWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> VnTracer for VnTraceWriter<W> {
+    fn trace(&mut self, record: &VnTraceRecord) {
+        if let Ok(line) = serde_json::to_string(record) {
+            let _ = writeln!(self.writer, "{}", line);
+        }
+    }
+}
+
+/// Reads records written by [`VnTraceWriter`].
+pub fn read_trace(reader: impl BufRead) -> Result<Vec<VnTraceRecord>, Box<dyn Error>> {
+    let mut result = vec![];
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+        if !line.is_empty() {
+            result.push(serde_json::from_str(line)?);
+        }
+    }
+    Ok(result)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VnCoverageItem {
+    pub position: usize,
+    pub line: Option<usize>,
+    pub description: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VnChapterCoverage {
+    pub chapter: String,
+    pub source: Option<String>,
+    pub actions: usize,
+    pub executed_actions: usize,
+    pub missed_actions: Vec<VnCoverageItem>,
+    pub missed_labels: Vec<VnCoverageItem>,
+}
+
+/// Maps trace records back onto story sources.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VnCoverageReport {
+    pub chapters: Vec<VnChapterCoverage>,
+}
+
+impl VnCoverageReport {
+    pub fn new<'a>(story: &VnStory, records: impl IntoIterator<Item = &'a VnTraceRecord>) -> Self {
+        let executed = records
+            .into_iter()
+            .map(|record| (record.chapter.as_str(), record.position))
+            .collect::<HashSet<_>>();
+        let chapters = story
+            .chapters
+            .iter()
+            .collect::<BTreeMap<_, _>>()
+            .into_iter()
+            .map(|(name, chapter)| {
+                let mut result = VnChapterCoverage {
+                    chapter: name.to_owned(),
+                    source: chapter.source.to_owned(),
+                    actions: 0,
+                    executed_actions: 0,
+                    missed_actions: vec![],
+                    missed_labels: vec![],
+                };
+                for (position, item) in chapter.items.iter().enumerate() {
+                    let hit = executed.contains(&(name.as_str(), position));
+                    match item {
+                        VnChapterItem::Label(label) => {
+                            if !hit {
+                                result.missed_labels.push(VnCoverageItem {
+                                    position,
+                                    line: chapter.line(position),
+                                    description: format!("${}", label),
+                                });
+                            }
+                        }
+                        VnChapterItem::Action(action) => {
+                            result.actions += 1;
+                            if hit {
+                                result.executed_actions += 1;
+                            } else {
+                                result.missed_actions.push(VnCoverageItem {
+                                    position,
+                                    line: chapter.line(position),
+                                    description: action.path(),
+                                });
+                            }
+                        }
+                    }
+                }
+                result
+            })
+            .collect();
+        Self { chapters }
+    }
+
+    pub fn actions(&self) -> usize {
+        self.chapters.iter().map(|chapter| chapter.actions).sum()
+    }
+
+    pub fn executed_actions(&self) -> usize {
+        self.chapters
+            .iter()
+            .map(|chapter| chapter.executed_actions)
+            .sum()
+    }
+}
+
+impl Display for VnCoverageReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Executed {} of {} actions",
+            self.executed_actions(),
+            self.actions()
+        )?;
+        for chapter in &self.chapters {
+            writeln!(
+                f,
+                "chapter {} ({}): {}/{}",
+                chapter.chapter,
+                chapter.source.as_deref().unwrap_or("?"),
+                chapter.executed_actions,
+                chapter.actions
+            )?;
+            for item in chapter
+                .missed_labels
+                .iter()
+                .chain(chapter.missed_actions.iter())
+            {
+                match item.line {
+                    Some(line) => writeln!(f, "  line {}: {}", line, item.description)?,
+                    None => writeln!(f, "  item {}: {}", item.position, item.description)?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_coverage() {
+        let story = crate::script::VnFile::parse(
+            r#"
+            chapter main {
+                jump label: end
+            $skipped:
+                set_global name: a value: 1
+            $end:
+                exit
+            }
+            "#,
+        )
+        .unwrap()
+        .story;
+        let records = [0, 3, 4].map(|position| VnTraceRecord {
+            thread: "main".to_owned(),
+            chapter: "main".to_owned(),
+            position,
+            item: VnTraceItem::Label {
+                name: Default::default(),
+            },
+        });
+        let report = VnCoverageReport::new(&story, &records);
+        assert_eq!(report.actions(), 3);
+        assert_eq!(report.executed_actions(), 2);
+        let chapter = &report.chapters[0];
+        assert_eq!(chapter.missed_labels[0].description, "$skipped");
+        assert_eq!(chapter.missed_labels[0].line, Some(4));
+        assert_eq!(chapter.missed_actions[0].description, "set_global");
+        assert_eq!(chapter.missed_actions[0].line, Some(5));
+    }
+
+    #[test]
+    fn test_trace_results() {
+        let story = crate::script::VnFile::parse(
+            r#"
+            chapter main {
+                vn_dialog.say what: "Which one?" choices: ["a" "b"]
+            }
+            "#,
+        )
+        .unwrap()
+        .story;
+        let recorder = Rc::new(RefCell::new(VnTraceRecorder::default()));
+        let mut harness = crate::harness::VnHarness::new(&story);
+        let vm = harness.vm();
+        vm.set_tracer(recorder.clone());
+        vm.enter("main", None);
+        for _ in 0..3 {
+            vm.run(10);
+        }
+        let records = &recorder.borrow().records;
+        assert_eq!(records.len(), 1);
+        let VnTraceItem::Action { result, .. } = &records[0].item else {
+            panic!("Expected action record!");
+        };
+        assert_eq!(result, &VnResult::Wait);
+        let line = serde_json::to_string(&records[0]).unwrap();
+        assert_eq!(
+            serde_json::from_str::<VnTraceRecord>(&line).unwrap(),
+            records[0]
+        );
+    }
+
+    #[test]
+    fn test_trace_params() {
+        let story = crate::script::VnFile::parse(
+            r#"
+            chapter main {
+                random name: roll max: 6
+            }
+            "#,
+        )
+        .unwrap()
+        .story;
+        let recorder = Rc::new(RefCell::new(VnTraceRecorder::default()));
+        let mut harness = crate::harness::VnHarness::new(&story);
+        let vm = harness.vm();
+        vm.set_tracer(recorder.clone());
+        vm.enter("main", None);
+        vm.run(10);
+        let records = &recorder.borrow().records;
+        let VnTraceItem::Action { params, .. } = &records[0].item else {
+            panic!("Expected action record!");
+        };
+        assert_eq!(params["name"], VnValue::Text("roll".to_owned()));
+        assert_eq!(params["min"], VnValue::Number(0.0));
+        assert_eq!(params["max"], VnValue::Integer(6));
+        assert_eq!(params["real"], VnValue::Boolean(false));
+    }
+}
@@ -1,32 +1,196 @@
-use crate::script::{VnChapter, VnChapterItem, VnResult, VnStory, VnValue};
+use crate::{
+    debugger::{DebugState, Stepping, VnBreakpoint, VnDebug, VnStackFrame},
+    localization::{VnLocalization, VN_LOCALIZATION},
+    random::VnRandom,
+    script::{VnChapter, VnChapterItem, VnEvent, VnResult, VnStory, VnToken, VnValue},
+    trace::{VnTraceItem, VnTraceRecord, VnTracer},
+};
 use intuicio_essentials::prelude::*;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
+    hash::{Hash, Hasher},
+};
 
 pub const VN_GLOBALS: &str = "vn-globals";
+pub const VN_MAIN_THREAD: &str = "main";
+pub const VN_CHOICE_GLOBAL: &str = "CHOICE";
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct Globals {
     pub properties: HashMap<String, VnValue>,
+    #[serde(default)]
+    pub random: VnRandom,
+    /// Names of globals that scripts want to be notified about.
+    #[serde(skip)]
+    pub watched: HashSet<String>,
+    /// Changes of observed globals detected during last [`Vm::run`] or [`Vm::step`].
+    #[serde(skip)]
+    pub changes: Vec<VnGlobalChange>,
+    /// Events waiting to be dispatched to their handler chapters.
+    #[serde(default)]
+    pub events: Vec<VnEvent>,
+    #[serde(default)]
+    tokens_generator: u64,
+    #[serde(default)]
+    resolved: HashSet<VnToken>,
+    /// Story time in seconds, advanced by host with [`Vm::advance_time`].
+    #[serde(default)]
+    pub time: f64,
+    #[serde(default)]
+    timers: Vec<Timer>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Timer {
+    token: VnToken,
+    until: f64,
+    skippable: bool,
+}
+
+impl Globals {
+    pub fn new_token(&mut self) -> VnToken {
+        let result = VnToken(self.tokens_generator);
+        self.tokens_generator += 1;
+        result
+    }
+
+    pub fn resolve(&mut self, token: VnToken) {
+        self.resolved.insert(token);
+    }
+
+    /// Story time left until earliest pending timer runs out.
+    pub fn next_timer(&self) -> Option<f64> {
+        self.timers
+            .iter()
+            .map(|timer| (timer.until - self.time).max(0.0))
+            .min_by(f64::total_cmp)
+    }
+
+    /// Creates token resolved after given amount of story time passes.
+    pub fn wait(&mut self, seconds: f64, skippable: bool) -> VnToken {
+        let token = self.new_token();
+        self.timers.push(Timer {
+            token,
+            until: self.time + seconds,
+            skippable,
+        });
+        token
+    }
+
+    fn resolve_timers(&mut self, mut f: impl FnMut(&Timer) -> bool) {
+        let resolved = &mut self.resolved;
+        self.timers.retain(|timer| {
+            if f(timer) {
+                resolved.insert(timer.token);
+                false
+            } else {
+                true
+            }
+        });
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VnGlobalChange {
+    pub name: String,
+    pub old: VnValue,
+    pub new: VnValue,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VnObserverId(usize);
+
+struct Observer {
+    id: VnObserverId,
+    name: String,
+    callback: Box<dyn FnMut(&VnGlobalChange)>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct State {
     chapter: String,
     position: usize,
+    /// Event handlers interrupt chapter below, so exiting them does not advance it.
+    #[serde(default)]
+    interrupt: bool,
+    #[serde(default)]
+    suspended: Option<VnToken>,
+    #[serde(default)]
+    joining: Option<String>,
+    /// Current action already asked to wait, so tracer records it only once.
+    #[serde(default)]
+    waiting: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Thread {
+    name: String,
+    state: Vec<State>,
+}
+
+/// Reason of [`Vm::run`] stopping.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VnRunResult {
+    /// No chapter left to execute.
+    Finished,
+    /// Some action waits for host to let it continue.
+    Suspended,
+    /// Steps budget got exhausted before story got suspended.
+    BudgetExhausted,
+    /// Story jumped back into the same state without anything that could change it.
+    InfiniteLoop,
+    /// Debugger paused execution.
+    Paused,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Execution {
+    Idle,
+    Blocked,
+    Progressed,
+    Jumped,
+}
+
+/// Save state of the story - execution state and globals.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VnSnapshot {
+    threads: Vec<Thread>,
+    globals: Globals,
+    /// Last seen values of observed globals, so restoring does not report false changes.
+    #[serde(default)]
+    observed: HashMap<String, VnValue>,
 }
 
 pub struct Vm {
     host: Host,
     chapters: HashMap<String, VnChapter>,
-    state: Vec<State>,
+    /// First thread is always the main one.
+    threads: Vec<Thread>,
+    observers: Vec<Observer>,
+    observers_id_generator: usize,
+    observed: HashMap<String, VnValue>,
+    debugger: DebugState,
+    tracer: Option<Box<dyn VnTracer>>,
 }
 
 impl Vm {
     pub fn new(mut host: Host) -> Self {
         host.context().set_custom(VN_GLOBALS, Globals::default());
+        host.context()
+            .set_custom(VN_LOCALIZATION, VnLocalization::default());
         Self {
             host,
             chapters: Default::default(),
-            state: vec![],
+            threads: vec![Thread {
+                name: VN_MAIN_THREAD.to_owned(),
+                state: vec![],
+            }],
+            observers: vec![],
+            observers_id_generator: 0,
+            observed: Default::default(),
+            debugger: Default::default(),
+            tracer: None,
         }
     }
 
@@ -40,23 +204,21 @@ impl Vm {
 
     pub fn enter(&mut self, chapter_name: &str, label: Option<&str>) -> bool {
         if let Some(chapter) = self.chapters.get(chapter_name) {
-            let mut position = 0;
-            if let Some(label) = label {
-                let index = chapter.items.iter().position(|item| {
-                    if let VnChapterItem::Label(name) = item {
-                        name == label
-                    } else {
-                        false
-                    }
-                });
-                if let Some(index) = index {
-                    position = index;
-                }
-            }
-            self.state.push(State {
+            let position = label
+                .and_then(|label| chapter.find_label(label))
+                .unwrap_or_default();
+            self.threads[0].state.push(State {
                 chapter: chapter_name.to_owned(),
                 position,
+                interrupt: false,
+                suspended: None,
+                joining: None,
+                waiting: false,
             });
+            push_event(
+                &mut self.host,
+                VnEvent::new(VnEvent::CHAPTER_ENTER, Some(chapter_name)),
+            );
             true
         } else {
             false
@@ -64,14 +226,208 @@ impl Vm {
     }
 
     pub fn exit(&mut self) {
-        self.state.pop();
-        if let Some(state) = self.state.last_mut() {
-            state.position += 1;
+        self.leave(0, true);
+    }
+
+    /// Starts chapter in background thread, replacing existing thread of that name.
+    pub fn spawn(&mut self, thread: &str, chapter_name: &str, label: Option<&str>) -> bool {
+        if thread == VN_MAIN_THREAD {
+            return false;
+        }
+        let chapter = match self.chapters.get(chapter_name) {
+            Some(chapter) => chapter,
+            None => return false,
+        };
+        let position = label
+            .and_then(|label| chapter.find_label(label))
+            .unwrap_or_default();
+        let state = State {
+            chapter: chapter_name.to_owned(),
+            position,
+            interrupt: false,
+            suspended: None,
+            joining: None,
+            waiting: false,
+        };
+        if let Some(item) = self.threads.iter_mut().find(|item| item.name == thread) {
+            item.state = vec![state];
+        } else {
+            self.threads.push(Thread {
+                name: thread.to_owned(),
+                state: vec![state],
+            });
+        }
+        push_event(
+            &mut self.host,
+            VnEvent::new(VnEvent::CHAPTER_ENTER, Some(chapter_name)),
+        );
+        true
+    }
+
+    pub fn kill(&mut self, thread: &str) {
+        if let Some(item) = self.threads.iter_mut().find(|item| item.name == thread) {
+            item.state.clear();
         }
     }
 
+    pub fn is_thread_running(&self, thread: &str) -> bool {
+        self.threads
+            .iter()
+            .any(|item| item.name == thread && !item.state.is_empty())
+    }
+
+    pub fn threads(&self) -> impl Iterator<Item = &str> {
+        self.threads
+            .iter()
+            .filter(|thread| !thread.state.is_empty())
+            .map(|thread| thread.name.as_str())
+    }
+
+    /// Raises event that enters all chapters with matching trigger.
+    pub fn emit(&mut self, name: &str, argument: Option<&str>) {
+        self.globals().events.push(VnEvent::new(name, argument));
+        self.dispatch_events();
+    }
+
     pub fn is_running(&self) -> bool {
-        !self.state.is_empty()
+        !self.threads[0].state.is_empty()
+    }
+
+    pub fn is_suspended(&self) -> bool {
+        self.threads[0]
+            .state
+            .last()
+            .map(|state| state.suspended.is_some())
+            .unwrap_or_default()
+    }
+
+    /// Lets suspended action continue.
+    pub fn resolve(&mut self, token: VnToken) {
+        self.globals().resolve(token);
+    }
+
+    pub fn advance_time(&mut self, delta_time: f64) {
+        let globals = self.globals();
+        globals.time += delta_time;
+        let time = globals.time;
+        globals.resolve_timers(|timer| timer.until <= time);
+    }
+
+    /// Resolves all waits that are allowed to be skipped.
+    pub fn skip_waits(&mut self) {
+        self.globals().resolve_timers(|timer| timer.skippable);
+    }
+
+    pub fn globals(&mut self) -> &mut Globals {
+        self.host
+            .context()
+            .custom_mut::<Globals>(VN_GLOBALS)
+            .expect("Cannot access VN globals!")
+    }
+
+    pub fn localization(&mut self) -> &mut VnLocalization {
+        self.host
+            .context()
+            .custom_mut::<VnLocalization>(VN_LOCALIZATION)
+            .expect("Cannot access VN localization!")
+    }
+
+    /// Selects language of texts in actions executed from now on, `None` means story texts.
+    pub fn set_language(&mut self, language: Option<&str>) {
+        self.localization().set_language(language);
+    }
+
+    pub fn seed(&mut self, seed: u64) {
+        self.globals().random.seed(seed);
+    }
+
+    /// Registers callback called after [`Vm::step`] that changed given global.
+    pub fn observe(
+        &mut self,
+        name: impl ToString,
+        callback: impl FnMut(&VnGlobalChange) + 'static,
+    ) -> VnObserverId {
+        let name = name.to_string();
+        let id = VnObserverId(self.observers_id_generator);
+        self.observers_id_generator += 1;
+        if !self.observed.contains_key(&name) {
+            let value = self
+                .globals()
+                .properties
+                .get(&name)
+                .cloned()
+                .unwrap_or_default();
+            self.observed.insert(name.to_owned(), value);
+        }
+        self.observers.push(Observer {
+            id,
+            name,
+            callback: Box::new(callback),
+        });
+        id
+    }
+
+    pub fn unobserve(&mut self, id: VnObserverId) {
+        let Some(index) = self.observers.iter().position(|observer| observer.id == id) else {
+            return;
+        };
+        let observer = self.observers.remove(index);
+        if !self.observed_names().contains(&observer.name) {
+            self.observed.remove(&observer.name);
+        }
+    }
+
+    /// Names of globals watched by observers, scripts or chapter triggers.
+    fn observed_names(&mut self) -> Vec<String> {
+        let globals = self
+            .host
+            .context()
+            .custom::<Globals>(VN_GLOBALS)
+            .expect("Cannot access VN globals!");
+        self.observers
+            .iter()
+            .map(|observer| observer.name.as_str())
+            .chain(globals.watched.iter().map(|name| name.as_str()))
+            .chain(
+                self.chapters
+                    .values()
+                    .flat_map(|chapter| chapter.triggers.iter())
+                    .filter(|trigger| trigger.event == VnEvent::GLOBAL_CHANGED)
+                    .filter_map(|trigger| trigger.argument.as_deref()),
+            )
+            .fold(vec![], |mut result, name| {
+                if !result.iter().any(|item| item == name) {
+                    result.push(name.to_owned());
+                }
+                result
+            })
+    }
+
+    /// Starts recording every executed label and action.
+    pub fn set_tracer(&mut self, tracer: impl VnTracer + 'static) {
+        self.tracer = Some(Box::new(tracer));
+    }
+
+    pub fn take_tracer(&mut self) -> Option<Box<dyn VnTracer>> {
+        self.tracer.take()
+    }
+
+    pub fn snapshot(&mut self) -> VnSnapshot {
+        VnSnapshot {
+            threads: self.threads.clone(),
+            globals: self.globals().clone(),
+            observed: self.observed.clone(),
+        }
+    }
+
+    pub fn restore(&mut self, snapshot: VnSnapshot) {
+        self.threads = snapshot.threads;
+        *self.globals() = snapshot.globals;
+        for (name, value) in snapshot.observed {
+            if let Some(observed) = self.observed.get_mut(&name) {
+                *observed = value;
+            }
+        }
     }
 
     pub fn chapters(&self) -> impl Iterator<Item = (&str, &VnChapter)> {
@@ -80,13 +436,34 @@ impl Vm {
             .map(|(name, chapter)| (name.as_str(), chapter))
     }
 
-    pub fn add_story(&mut self, story: &VnStory) {
+    /// Adds chapters of story once it passes [`VnStory::validate_actions`].
+    /// Validation is mandatory, because actions panic when they get params of invalid type.
+    pub fn add_story(&mut self, story: &VnStory) -> Result<(), Vec<String>> {
+        let (_, registry) = self.host.context_and_registry();
+        story.validate_actions(registry)?;
         for (name, chapter) in &story.chapters {
             self.add_chapter(name, chapter.clone());
         }
+        Ok(())
     }
 
     pub fn add_chapter(&mut self, name: impl ToString, chapter: VnChapter) {
+        for trigger in &chapter.triggers {
+            if trigger.event != VnEvent::GLOBAL_CHANGED {
+                continue;
+            }
+            if let Some(name) = trigger.argument.as_ref() {
+                if !self.observed.contains_key(name) {
+                    let value = self
+                        .globals()
+                        .properties
+                        .get(name)
+                        .cloned()
+                        .unwrap_or_default();
+                    self.observed.insert(name.to_owned(), value);
+                }
+            }
+        }
         self.chapters.insert(name.to_string(), chapter);
     }
 
@@ -107,91 +484,345 @@ impl Vm {
     }
 
     pub fn step(&mut self) {
-        let state = match self.state.last_mut() {
+        self.globals().changes.clear();
+        self.step_execution();
+    }
+
+    /// Steps until story suspends, finishes or exhausts steps budget.
+    pub fn run(&mut self, max_steps: usize) -> VnRunResult {
+        self.globals().changes.clear();
+        let mut visited = HashSet::new();
+        for _ in 0..max_steps {
+            let (main, execution) = self.step_execution();
+            if !self.is_running() {
+                return VnRunResult::Finished;
+            }
+            if self.debugger.paused {
+                return VnRunResult::Paused;
+            }
+            match execution {
+                Execution::Idle | Execution::Progressed => {}
+                Execution::Blocked => return VnRunResult::Suspended,
+                Execution::Jumped => {
+                    if !visited.insert(self.state_hash()) {
+                        // Background threads looping while main one waits just yield.
+                        if main == Execution::Blocked {
+                            return VnRunResult::Suspended;
+                        }
+                        return VnRunResult::InfiniteLoop;
+                    }
+                }
+            }
+        }
+        VnRunResult::BudgetExhausted
+    }
+
+    /// Hash of threads positions and globals, same for states story cannot tell apart.
+    fn state_hash(&mut self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        for thread in &self.threads {
+            thread.state.len().hash(&mut hasher);
+            for state in &thread.state {
+                state.chapter.hash(&mut hasher);
+                state.position.hash(&mut hasher);
+            }
+        }
+        let globals = self.globals();
+        hash_properties(&globals.properties, &mut hasher);
+        globals.random.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Returns execution of main thread and combined execution of all threads.
+    fn step_execution(&mut self) -> (Execution, Execution) {
+        let mut main = Execution::Idle;
+        let mut result = Execution::Idle;
+        // Threads spawned in this step start executing in the next one.
+        for index in 0..self.threads.len() {
+            if self.debugger.paused {
+                return (Execution::Blocked, Execution::Blocked);
+            }
+            let execution = self.execute(index);
+            if index == 0 {
+                main = execution;
+            }
+            result = result.max(execution);
+        }
+        self.threads
+            .retain(|thread| thread.name == VN_MAIN_THREAD || !thread.state.is_empty());
+        self.observe_globals();
+        self.dispatch_events();
+        (main, result)
+    }
+
+    fn leave(&mut self, thread: usize, advance: bool) {
+        let thread = &mut self.threads[thread];
+        if let Some(state) = thread.state.pop() {
+            if state.interrupt {
+                return;
+            }
+            push_event(
+                &mut self.host,
+                VnEvent::new(VnEvent::CHAPTER_EXIT, Some(&state.chapter)),
+            );
+            if advance {
+                if let Some(state) = thread.state.last_mut() {
+                    state.position += 1;
+                }
+            }
+        }
+    }
+
+    fn dispatch_events(&mut self) {
+        let events = std::mem::take(&mut self.globals().events);
+        for event in events {
+            let mut handlers = self
+                .chapters
+                .iter()
+                .filter(|(_, chapter)| {
+                    chapter
+                        .triggers
+                        .iter()
+                        .any(|trigger| trigger.matches(&event))
+                })
+                .map(|(name, _)| name.to_owned())
+                .collect::<Vec<_>>();
+            handlers.sort();
+            // Handlers run in name order, so last one has to be at the bottom.
+            for name in handlers.into_iter().rev() {
+                self.threads[0].state.push(State {
+                    chapter: name,
+                    position: 0,
+                    interrupt: true,
+                    suspended: None,
+                    joining: None,
+                    waiting: false,
+                });
+            }
+        }
+    }
+
+    fn observe_globals(&mut self) {
+        let names = self.observed_names();
+        // Globals nobody watches anymore start fresh once watched again.
+        self.observed.retain(|name, _| names.contains(name));
+        let globals = self
+            .host
+            .context()
+            .custom_mut::<Globals>(VN_GLOBALS)
+            .expect("Cannot access VN globals!");
+        let mut changes = vec![];
+        for name in names {
+            let current = globals.properties.get(&name).cloned().unwrap_or_default();
+            if let Some(previous) = self.observed.get_mut(&name) {
+                if *previous != current {
+                    changes.push(VnGlobalChange {
+                        old: std::mem::replace(previous, current.clone()),
+                        new: current,
+                        name,
+                    });
+                }
+            } else {
+                self.observed.insert(name, current);
+            }
+        }
+        globals.events.extend(
+            changes
+                .iter()
+                .map(|change| VnEvent::new(VnEvent::GLOBAL_CHANGED, Some(&change.name))),
+        );
+        for change in &changes {
+            for observer in &mut self.observers {
+                if observer.name == change.name {
+                    (observer.callback)(change);
+                }
+            }
+        }
+        globals.changes.extend(changes);
+    }
+
+    fn execute(&mut self, thread: usize) -> Execution {
+        let joining = self.threads[thread]
+            .state
+            .last()
+            .and_then(|state| state.joining.to_owned());
+        if let Some(name) = joining {
+            // Joining itself never completes, story validation reports it.
+            if name != self.threads[thread].name && self.is_thread_running(&name) {
+                return Execution::Blocked;
+            }
+            if let Some(state) = self.threads[thread].state.last_mut() {
+                state.joining = None;
+            }
+        }
+        let depth = self.threads[thread].state.len();
+        let thread_name = self
+            .tracer
+            .as_ref()
+            .map(|_| self.threads[thread].name.to_owned());
+        let state = match self.threads[thread].state.last_mut() {
             Some(state) => state,
-            None => return,
+            None => return Execution::Idle,
         };
+        if let Some(token) = state.suspended {
+            let globals = self
+                .host
+                .context()
+                .custom_mut::<Globals>(VN_GLOBALS)
+                .expect("Cannot access VN globals!");
+            if globals.resolved.remove(&token) {
+                state.suspended = None;
+            } else {
+                return Execution::Blocked;
+            }
+        }
         let chapter = match self.chapters.get(&state.chapter) {
             Some(chapter) => chapter,
             None => {
-                self.state.pop();
-                return;
+                self.leave(thread, false);
+                return Execution::Progressed;
             }
         };
         let item = match chapter.items.get(state.position) {
             Some(item) => item,
             None => {
-                self.state.pop();
-                return;
+                self.leave(thread, false);
+                return Execution::Progressed;
             }
         };
+        if self
+            .debugger
+            .should_pause(thread, depth, &state.chapter, chapter, state.position)
+        {
+            self.debugger
+                .pause_at(thread, depth, &state.chapter, state.position);
+            return Execution::Blocked;
+        }
         match item {
-            VnChapterItem::Label(_) => {
+            VnChapterItem::Label(name) => {
+                if let (Some(tracer), Some(thread_name)) = (self.tracer.as_mut(), thread_name) {
+                    tracer.trace(&VnTraceRecord {
+                        thread: thread_name,
+                        chapter: state.chapter.to_owned(),
+                        position: state.position,
+                        item: VnTraceItem::Label {
+                            name: name.to_owned(),
+                        },
+                    });
+                }
                 state.position += 1;
+                Execution::Progressed
             }
             VnChapterItem::Action(action) => {
                 let (context, registry) = self.host.context_and_registry();
-                match action.evaluate(context, registry) {
+                let localized = context
+                    .custom::<VnLocalization>(VN_LOCALIZATION)
+                    .and_then(|localization| localization.localize(action));
+                let action = localized.as_ref().unwrap_or(action);
+                let result = action.evaluate(context, registry);
+                let waiting = matches!(result, VnResult::Wait);
+                if let (Some(tracer), Some(thread_name)) = (self.tracer.as_mut(), thread_name) {
+                    if !(waiting && state.waiting) {
+                        tracer.trace(&VnTraceRecord {
+                            thread: thread_name,
+                            chapter: state.chapter.to_owned(),
+                            position: state.position,
+                            item: VnTraceItem::Action {
+                                function: action.path(),
+                                params: action.resolved_params(registry),
+                                result: result.to_owned(),
+                            },
+                        });
+                    }
+                }
+                state.waiting = waiting;
+                match result {
                     VnResult::Continue => {
                         state.position += 1;
+                        Execution::Progressed
+                    }
+                    VnResult::Wait => Execution::Blocked,
+                    VnResult::Suspend(token) => {
+                        state.position += 1;
+                        state.suspended = Some(token);
+                        Execution::Progressed
                     }
                     VnResult::JumpTo {
                         chapter: chapter_name,
                         label,
                     } => {
-                        let chapter_name = chapter_name.as_deref().unwrap_or(&state.chapter);
-                        if let Some(chapter) = self.chapters.get(chapter_name) {
-                            state.chapter = chapter_name.to_owned();
-                            state.position = 0;
-                            if let Some(label) = label {
-                                let index = chapter.items.iter().position(|item| {
-                                    if let VnChapterItem::Label(name) = item {
-                                        name == &label
-                                    } else {
-                                        false
-                                    }
-                                });
-                                if let Some(index) = index {
-                                    state.position = index;
-                                }
+                        let chapter_name = chapter_name.unwrap_or_else(|| state.chapter.to_owned());
+                        if let Some(chapter) = self.chapters.get(&chapter_name) {
+                            if chapter_name != state.chapter && !state.interrupt {
+                                push_event(
+                                    &mut self.host,
+                                    VnEvent::new(VnEvent::CHAPTER_EXIT, Some(&state.chapter)),
+                                );
+                                push_event(
+                                    &mut self.host,
+                                    VnEvent::new(VnEvent::CHAPTER_ENTER, Some(&chapter_name)),
+                                );
                             }
+                            state.position = label
+                                .and_then(|label| chapter.find_label(&label))
+                                .unwrap_or_default();
+                            state.chapter = chapter_name;
+                            Execution::Jumped
                         } else {
                             state.position += 1;
+                            Execution::Progressed
                         }
                     }
                     VnResult::Enter {
                         chapter: chapter_name,
                         label,
                     } => {
-                        let chapter_name =
-                            chapter_name.as_deref().unwrap_or(&state.chapter).to_owned();
+                        let chapter_name = chapter_name.unwrap_or_else(|| state.chapter.to_owned());
                         if let Some(chapter) = self.chapters.get(&chapter_name) {
-                            let mut position = 0;
-                            if let Some(label) = label {
-                                let index = chapter.items.iter().position(|item| {
-                                    if let VnChapterItem::Label(name) = item {
-                                        name == &label
-                                    } else {
-                                        false
-                                    }
-                                });
-                                if let Some(index) = index {
-                                    position = index;
-                                }
-                            }
-                            self.state.push(State {
+                            let position = label
+                                .and_then(|label| chapter.find_label(&label))
+                                .unwrap_or_default();
+                            push_event(
+                                &mut self.host,
+                                VnEvent::new(VnEvent::CHAPTER_ENTER, Some(&chapter_name)),
+                            );
+                            self.threads[thread].state.push(State {
                                 chapter: chapter_name,
                                 position,
+                                interrupt: false,
+                                suspended: None,
+                                joining: None,
+                                waiting: false,
                             });
+                            Execution::Progressed
                         } else {
                             state.position += 1;
+                            Execution::Progressed
                         }
                     }
                     VnResult::Exit => {
-                        self.state.pop();
-                        if let Some(state) = self.state.last_mut() {
-                            state.position += 1;
-                        }
+                        self.leave(thread, true);
+                        Execution::Progressed
+                    }
+                    VnResult::Spawn {
+                        thread: name,
+                        chapter: chapter_name,
+                        label,
+                    } => {
+                        state.position += 1;
+                        let chapter_name = chapter_name.unwrap_or_else(|| state.chapter.to_owned());
+                        self.spawn(&name, &chapter_name, label.as_deref());
+                        Execution::Progressed
+                    }
+                    VnResult::Kill { thread: name } => {
+                        state.position += 1;
+                        self.kill(&name);
+                        Execution::Progressed
+                    }
+                    VnResult::Join { thread: name } => {
+                        state.position += 1;
+                        state.joining = Some(name);
+                        Execution::Progressed
                     }
                 }
             }
@@ -199,67 +830,185 @@ impl Vm {
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::script::*;
-    use intuicio_essentials::core as intuicio_core;
-    use intuicio_essentials::data as intuicio_data;
-
-    #[intuicio_function()]
-    fn show_screen(name: VnValue, module_name: VnValue) -> VnResult {
-        println!(
-            "show_screen | name: {:?} | module_name: {:?}",
-            name, module_name
-        );
-        VnResult::Continue
+impl VnDebug for Vm {
+    fn breakpoints(&self) -> Vec<VnBreakpoint> {
+        self.debugger.breakpoints.clone()
     }
 
-    #[intuicio_function()]
-    fn hide_screen(name: VnValue, module_name: VnValue) -> VnResult {
-        println!(
-            "hide_screen | name: {:?} | module_name: {:?}",
-            name, module_name
-        );
-        VnResult::Continue
+    fn add_breakpoint(&mut self, breakpoint: VnBreakpoint) {
+        if !self.debugger.breakpoints.contains(&breakpoint) {
+            self.debugger.breakpoints.push(breakpoint);
+        }
     }
 
-    #[intuicio_function()]
-    fn scene(name: VnValue) -> VnResult {
-        println!("scene | name: {:?}", name);
-        VnResult::Continue
+    fn remove_breakpoint(&mut self, breakpoint: &VnBreakpoint) {
+        self.debugger.breakpoints.retain(|item| item != breakpoint);
     }
 
-    #[intuicio_function()]
-    fn show(character: VnValue, variant: VnValue) -> VnResult {
-        println!("show | character: {:?} | variant: {:?}", character, variant);
-        VnResult::Continue
+    fn clear_breakpoints(&mut self) {
+        self.debugger.breakpoints.clear();
     }
 
-    #[intuicio_function()]
-    fn hide(character: VnValue) -> VnResult {
-        println!("show | character: {:?}", character);
-        VnResult::Continue
+    fn is_paused(&self) -> bool {
+        self.debugger.paused
     }
 
-    #[intuicio_function()]
-    fn say(what: VnValue, who: VnValue, choices: VnValue) -> VnResult {
-        println!(
-            "say | what: {:?} | who: {:?} | choices: {:?}",
-            what, who, choices
-        );
-        VnResult::Continue
+    fn pause(&mut self) {
+        self.debugger.paused = true;
+        self.debugger.paused_at = Some((0, self.threads[0].state.len()));
+        self.debugger.stepping = None;
+        self.debugger.skip = None;
+    }
+
+    fn resume(&mut self) {
+        self.debugger.resume(None);
+    }
+
+    fn step_into(&mut self) {
+        self.debugger.resume(Some(Stepping::Into));
+    }
+
+    fn step_over(&mut self) {
+        self.debugger.resume(Some(Stepping::Over));
     }
 
-    fn install(registry: &mut Registry) {
-        registry.add_function(show_screen::define_function(registry));
-        registry.add_function(hide_screen::define_function(registry));
-        registry.add_function(scene::define_function(registry));
-        registry.add_function(show::define_function(registry));
-        registry.add_function(hide::define_function(registry));
-        registry.add_function(say::define_function(registry));
+    fn step_out(&mut self) {
+        self.debugger.resume(Some(Stepping::Out));
     }
 
+    fn stack(&self) -> Vec<VnStackFrame> {
+        self.threads
+            .iter()
+            .flat_map(|thread| {
+                thread.state.iter().map(|state| VnStackFrame {
+                    thread: thread.name.to_owned(),
+                    chapter: state.chapter.to_owned(),
+                    position: state.position,
+                    label: self.chapters.get(&state.chapter).and_then(|chapter| {
+                        chapter
+                            .items
+                            .iter()
+                            .take(state.position + 1)
+                            .rev()
+                            .find_map(|item| match item {
+                                VnChapterItem::Label(name) => Some(name.to_owned()),
+                                _ => None,
+                            })
+                    }),
+                })
+            })
+            .collect()
+    }
+
+    fn global(&mut self, name: &str) -> Option<VnValue> {
+        self.globals().properties.get(name).cloned()
+    }
+
+    fn global_properties(&mut self) -> HashMap<String, VnValue> {
+        self.globals().properties.clone()
+    }
+
+    fn set_global(&mut self, name: &str, value: VnValue) {
+        self.globals().properties.insert(name.to_owned(), value);
+    }
+
+    fn delete_global(&mut self, name: &str) {
+        self.globals().properties.remove(name);
+    }
+}
+
+fn push_event(host: &mut Host, event: VnEvent) {
+    host.context()
+        .custom_mut::<Globals>(VN_GLOBALS)
+        .expect("Cannot access VN globals!")
+        .events
+        .push(event);
+}
+
+/// Independent of map iteration order.
+fn hash_properties(properties: &HashMap<String, VnValue>, state: &mut impl Hasher) {
+    let mut result = 0u64;
+    for (key, value) in properties {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        hash_value(value, &mut hasher);
+        result = result.wrapping_add(hasher.finish());
+    }
+    properties.len().hash(state);
+    result.hash(state);
+}
+
+/// Integers hash like equal numbers, since values compare that way.
+fn hash_value(value: &VnValue, state: &mut impl Hasher) {
+    let number = |value: f64, state: &mut _| {
+        // Both zeros compare equal.
+        (if value == 0.0 { 0.0 } else { value })
+            .to_bits()
+            .hash(state)
+    };
+    match value {
+        VnValue::None => 0u8.hash(state),
+        VnValue::Boolean(value) => {
+            1u8.hash(state);
+            value.hash(state);
+        }
+        VnValue::Integer(value) => {
+            2u8.hash(state);
+            number(*value as f64, state);
+        }
+        VnValue::Number(value) => {
+            2u8.hash(state);
+            number(*value, state);
+        }
+        VnValue::Text(value) => {
+            3u8.hash(state);
+            value.hash(state);
+        }
+        VnValue::Color(value) => {
+            4u8.hash(state);
+            value.hash(state);
+        }
+        VnValue::Vec2 { x, y } => {
+            5u8.hash(state);
+            number(*x, state);
+            number(*y, state);
+        }
+        VnValue::Rect {
+            x,
+            y,
+            width,
+            height,
+        } => {
+            6u8.hash(state);
+            number(*x, state);
+            number(*y, state);
+            number(*width, state);
+            number(*height, state);
+        }
+        VnValue::Reference { kind, name } => {
+            7u8.hash(state);
+            kind.hash(state);
+            name.hash(state);
+        }
+        VnValue::Array(values) => {
+            8u8.hash(state);
+            values.len().hash(state);
+            for value in values {
+                hash_value(value, state);
+            }
+        }
+        VnValue::Map(properties) => {
+            9u8.hash(state);
+            hash_properties(properties, state);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{harness::VnHarness, script::*};
+
     #[test]
     fn test_vm() {
         let mut content_provider = ExtensionContentProvider::<VnFile>::default()
@@ -270,16 +1019,324 @@ mod tests {
             .default_extension("vns");
         let story = VnPackage::new("../resources/main.vns", &mut content_provider).unwrap();
         let story = story.compile();
+
+        let mut harness = VnHarness::new(&story).choices([0]);
+        let transcript = harness.run("welcome").unwrap();
+        assert_eq!(transcript.scenes().collect::<Vec<_>>(), vec!["street"]);
+        assert!(transcript.said(Some("rin"), "Oh, me too!"));
+        assert!(transcript.said(Some("narrator"), "- THE END -"));
+
+        let mut harness = VnHarness::new(&story).choices([1]);
+        let transcript = harness.run("welcome").unwrap();
+        assert!(transcript.said(Some("rin"), "Sad to hear that!"));
+        assert!(!transcript.said(Some("rin"), "Oh, me too!"));
+    }
+
+    fn make_vm(content: &str) -> Vm {
         let mut registry = Registry::default().with_basic_types();
         crate::library::install(&mut registry);
-        install(&mut registry);
         let host = Host::new(Context::new(1024, 1024, 1024), registry.into());
         let mut vm = Vm::new(host);
+        vm.add_story(&VnFile::parse(content).unwrap().story)
+            .unwrap();
+        vm
+    }
 
-        vm.add_story(&story);
-        vm.enter("welcome", None);
+    #[test]
+    fn test_observers() {
+        let mut vm = make_vm(
+            r#"
+            chapter main {
+                set_global name: score value: 1
+                set_global name: score value: 1
+                exit
+            }
+            "#,
+        );
+        let changes = std::rc::Rc::new(std::cell::RefCell::new(vec![]));
+        let changes2 = changes.clone();
+        vm.observe("score", move |change| {
+            changes2.borrow_mut().push(change.clone())
+        });
+        vm.enter("main", None);
+        while vm.is_running() {
+            vm.step();
+        }
+        assert_eq!(
+            changes.borrow().as_slice(),
+            &[VnGlobalChange {
+                name: "score".to_owned(),
+                old: VnValue::None,
+                new: VnValue::Number(1.0),
+            }]
+        );
+
+        let id = vm.observe("lives", |_| {});
+        vm.unobserve(id);
+        vm.globals()
+            .properties
+            .insert("lives".to_owned(), VnValue::Integer(3));
+        vm.step();
+        let changes2 = changes.clone();
+        vm.observe("lives", move |change| {
+            changes2.borrow_mut().push(change.clone())
+        });
+        vm.step();
+        assert_eq!(changes.borrow().len(), 1);
+
+        let mut vm = make_vm(
+            r#"
+            chapter main {
+                set_global name: score value: 1
+                set_global name: score value: 2
+                set_global name: score value: 3
+                wait seconds: 10
+            }
+            "#,
+        );
+        vm.globals().watched.insert("score".to_owned());
+        vm.enter("main", None);
+        assert_eq!(vm.run(100), VnRunResult::Suspended);
+        assert_eq!(
+            vm.globals()
+                .changes
+                .iter()
+                .map(|change| change.new.to_owned())
+                .collect::<Vec<_>>(),
+            vec![VnValue::Integer(2), VnValue::Integer(3)]
+        );
+        vm.run(100);
+        assert!(vm.globals().changes.is_empty());
+    }
+
+    #[test]
+    fn test_events() {
+        let mut vm = make_vm(
+            r#"
+            chapter main {
+                set_global name: score value: 1
+                emit name: custom
+                exit
+            }
+
+            chapter on_enter on chapter_enter main {
+                set_global name: entered value: true
+                exit
+            }
+
+            chapter on_score on global_changed score {
+                set_global name: scored value: true
+                exit
+            }
+
+            chapter on_custom on custom {
+                set_global name: emitted value: true
+                exit
+            }
+            "#,
+        );
+        vm.enter("main", None);
+        while vm.is_running() {
+            vm.step();
+        }
+        let globals = vm.globals();
+        assert_eq!(
+            globals.properties.get("entered"),
+            Some(&VnValue::Boolean(true))
+        );
+        assert_eq!(
+            globals.properties.get("scored"),
+            Some(&VnValue::Boolean(true))
+        );
+        assert_eq!(
+            globals.properties.get("emitted"),
+            Some(&VnValue::Boolean(true))
+        );
+    }
+
+    #[test]
+    fn test_run() {
+        let mut vm = make_vm(
+            r#"
+            chapter main {
+                set_global name: counter value: 0
+            $loop:
+                set_global name: counter value: 1
+                jump label: loop
+            }
+
+            chapter countdown {
+                set_global name: a value: 1
+                set_global name: b value: 2
+                set_global name: c value: 3
+                exit
+            }
+            "#,
+        );
+        vm.enter("main", None);
+        assert_eq!(vm.run(1000), VnRunResult::InfiniteLoop);
+        vm.exit();
+        vm.enter("countdown", None);
+        assert_eq!(vm.run(2), VnRunResult::BudgetExhausted);
+        assert_eq!(vm.run(1000), VnRunResult::Finished);
+    }
+
+    #[test]
+    fn test_threads() {
+        let mut vm = make_vm(
+            r#"
+            chapter main {
+                spawn thread: ambient chapter: ambient
+                spawn thread: worker chapter: worker
+                join thread: worker
+                kill thread: ambient
+                exit
+            }
+
+            chapter ambient {
+            $loop:
+                set_global name: ambient value: true
+                jump label: loop
+            }
+
+            chapter worker {
+                set_global name: a value: 1
+                set_global name: b value: 2
+                exit
+            }
+            "#,
+        );
+        vm.enter("main", None);
         while vm.is_running() {
             vm.step();
         }
+        assert!(!vm.is_thread_running("ambient"));
+        assert!(!vm.is_thread_running("worker"));
+        let globals = vm.globals();
+        assert_eq!(
+            globals.properties.get("ambient"),
+            Some(&VnValue::Boolean(true))
+        );
+        assert_eq!(globals.properties.get("b"), Some(&VnValue::Number(2.0)));
+
+        let mut vm = make_vm(
+            r#"
+            chapter main {
+                spawn thread: ambient chapter: ambient
+                wait seconds: 10
+                exit
+            }
+
+            chapter ambient {
+            $loop:
+                set_global name: ambient value: true
+                jump label: loop
+            }
+            "#,
+        );
+        vm.enter("main", None);
+        assert_eq!(vm.run(1000), VnRunResult::Suspended);
+        assert!(vm.is_thread_running("ambient"));
+    }
+
+    #[test]
+    fn test_join_itself() {
+        let mut vm = make_vm(
+            r#"
+            chapter main {
+                join thread: main
+                set_global name: joined value: true
+            }
+            "#,
+        );
+        vm.enter("main", None);
+        assert_eq!(vm.run(1000), VnRunResult::Finished);
+        assert_eq!(
+            vm.globals().properties.get("joined"),
+            Some(&VnValue::Boolean(true))
+        );
+
+        let errors = VnFile::parse(
+            r#"
+            chapter main {
+                spawn thread: worker chapter: worker
+                join thread: worker
+            }
+
+            chapter worker {
+                join thread: worker
+            }
+            "#,
+        )
+        .unwrap()
+        .story
+        .validate()
+        .unwrap_err();
+        assert_eq!(
+            errors,
+            vec!["chapter worker item 0 thread: joins its own `worker` thread"]
+        );
+    }
+
+    #[test]
+    fn test_wait() {
+        let mut vm = make_vm(
+            r#"
+            chapter main {
+                wait seconds: 2 skippable: false
+                wait seconds: 100
+                exit
+            }
+            "#,
+        );
+        vm.enter("main", None);
+        assert_eq!(vm.run(1000), VnRunResult::Suspended);
+        vm.skip_waits();
+        vm.advance_time(1.0);
+        assert_eq!(vm.run(1000), VnRunResult::Suspended);
+        vm.advance_time(1.5);
+        assert_eq!(vm.run(1000), VnRunResult::Suspended);
+        vm.skip_waits();
+        assert_eq!(vm.run(1000), VnRunResult::Finished);
+    }
+
+    #[test]
+    fn test_debugger() {
+        let mut vm = make_vm(
+            r#"
+            chapter main {
+                set_global name: a value: 1
+                enter chapter: nested
+            $second:
+                set_global name: b value: 2
+                exit
+            }
+
+            chapter nested {
+                set_global name: c value: 3
+                exit
+            }
+            "#,
+        );
+        vm.add_breakpoint(VnBreakpoint::Action {
+            chapter: "main".to_owned(),
+            position: 1,
+        });
+        vm.enter("main", None);
+        assert_eq!(vm.run(1000), VnRunResult::Paused);
+        let frames = vm.stack();
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].position, 1);
+        assert_eq!(vm.global("a"), Some(VnValue::Number(1.0)));
+        vm.step_over();
+        assert_eq!(vm.run(1000), VnRunResult::Paused);
+        assert_eq!(vm.stack()[0].label.as_deref(), Some("second"));
+        assert_eq!(vm.global("c"), Some(VnValue::Number(3.0)));
+        vm.set_global("b", VnValue::Number(42.0));
+        vm.step_into();
+        assert_eq!(vm.run(1000), VnRunResult::Paused);
+        vm.resume();
+        assert_eq!(vm.run(1000), VnRunResult::Finished);
+        assert_eq!(vm.global("b"), Some(VnValue::Number(2.0)));
     }
 }
@@ -1,6 +1,11 @@
 use intuicio_essentials::prelude::*;
 use intuicio_frontend_simpleton::prelude::*;
-use std::{cell::RefCell, collections::HashMap};
+use serde::{de::Error as _, Deserialize, Deserializer};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    net::{TcpListener, TcpStream},
+};
 use tetra::{
     graphics::{
         self,
@@ -17,6 +22,9 @@ use vngineer_core::prelude::*;
 use vngineer_simpleton::*;
 
 pub const GAME_GLOBALS: &str = "game-globals";
+const STEPS_PER_FRAME: usize = 1024;
+/// Line height relative to font size.
+const LINE_SPACING: f32 = 1.2;
 
 #[derive(Debug, Clone)]
 pub struct Screen {
@@ -24,6 +32,52 @@ pub struct Screen {
     pub module_name: String,
 }
 
+pub fn value_to_color(value: &VnValue) -> Option<Color> {
+    let (r, g, b, a) = value.as_rgba_normalized()?;
+    Some(Color::rgba(r as f32, g as f32, b as f32, a as f32))
+}
+
+fn deserialize_color<'de, D>(deserializer: D) -> Result<Option<Color>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<VnValue>::deserialize(deserializer)?
+        .map(|value| value_to_color(&value).ok_or_else(|| D::Error::custom("expected color")))
+        .transpose()
+}
+
+/// Color with its alpha multiplied by visibility.
+fn fade(color: Color, visibility: f32) -> Color {
+    color.with_alpha(color.a * visibility)
+}
+
+/// Either `vec2(x, y)` or `{x: ... y: ...}` with any of components skipped.
+#[derive(Debug, Default, Deserialize)]
+struct VectorProperty {
+    x: Option<f32>,
+    y: Option<f32>,
+}
+
+impl VectorProperty {
+    fn or(property: Option<Self>, x: f32, y: f32) -> Vec2<f32> {
+        let property = property.unwrap_or_default();
+        Vec2::new(property.x.unwrap_or(x), property.y.unwrap_or(y))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CharacterProperties {
+    name: String,
+    variants: HashMap<String, String>,
+    position: Option<VectorProperty>,
+    rotation: Option<f32>,
+    scale: Option<VectorProperty>,
+    alignment: Option<VectorProperty>,
+    #[serde(default, deserialize_with = "deserialize_color")]
+    tint: Option<Color>,
+    effect: Option<String>,
+}
+
 #[derive(Debug, Clone)]
 pub struct Character {
     pub name: String,
@@ -32,165 +86,52 @@ pub struct Character {
     pub rotation: f32,
     pub scale: Vec2<f32>,
     pub alignment: Vec2<f32>,
+    pub tint: Color,
     pub effect: Option<String>,
 }
 
 impl Character {
     pub fn new(id: &str, properties: &HashMap<String, VnValue>) -> Self {
+        let properties =
+            from_vn_properties::<CharacterProperties>(&format!("character.{}", id), properties)
+                .unwrap_or_else(|error| panic!("Invalid character: {}", error));
         Self {
-            name: properties
-                .get("name")
-                .map(|name| {
-                    name.as_text()
-                        .unwrap_or_else(|| panic!("Character '{}' `name` is not a text!", id))
-                        .to_owned()
-                })
-                .unwrap_or_else(|| panic!("Character '{}' does not have `name` property!", id)),
-            variants: properties
-                .get("variants")
-                .map(|variants| {
-                    variants
-                        .as_map()
-                        .unwrap_or_else(|| panic!("Character '{}' `variants` is not a map!", id))
-                        .iter()
-                        .map(|(key, value)| {
-                            (
-                                key.to_owned(),
-                                value
-                                    .as_text()
-                                    .unwrap_or_else(|| {
-                                        panic!(
-                                            "Character '{}' variant '{}' is not a text!",
-                                            id, key
-                                        )
-                                    })
-                                    .to_owned(),
-                            )
-                        })
-                        .collect()
-                })
-                .unwrap_or_else(|| panic!("Character '{}' does not have `name` property!", id)),
-            position: properties
-                .get("position")
-                .map(|position| {
-                    let position = position
-                        .as_map()
-                        .unwrap_or_else(|| panic!("Character '{}' `position` is not a map!", id));
-                    let x = position
-                        .get("x")
-                        .map(|x| {
-                            x.as_number().unwrap_or_else(|| {
-                                panic!("Character '{}' `position.x` is not a number!", id)
-                            })
-                        })
-                        .unwrap_or_default() as f32;
-                    let y = position
-                        .get("y")
-                        .map(|y| {
-                            y.as_number().unwrap_or_else(|| {
-                                panic!("Character '{}' `position.y` is not a number!", id)
-                            })
-                        })
-                        .unwrap_or_default() as f32;
-                    (x, y)
-                })
-                .unwrap_or_default()
-                .into(),
-            rotation: properties
-                .get("rotation")
-                .map(|rotation| {
-                    rotation
-                        .as_number()
-                        .unwrap_or_else(|| panic!("Character '{}' `rotation is not a number!", id))
-                        as f32
-                })
-                .unwrap_or_default(),
-            scale: properties
-                .get("scale")
-                .map(|scale| {
-                    let scale = scale
-                        .as_map()
-                        .unwrap_or_else(|| panic!("Character '{}' `scale` is not a map!", id));
-                    let x = scale
-                        .get("x")
-                        .map(|x| {
-                            x.as_number().unwrap_or_else(|| {
-                                panic!("Character '{}' `scale.x` is not a number!", id)
-                            })
-                        })
-                        .unwrap_or(1.0) as f32;
-                    let y = scale
-                        .get("y")
-                        .map(|y| {
-                            y.as_number().unwrap_or_else(|| {
-                                panic!("Character '{}' `scale.y` is not a number!", id)
-                            })
-                        })
-                        .unwrap_or(1.0) as f32;
-                    (x, y)
-                })
-                .unwrap_or((1.0, 1.0))
-                .into(),
-            alignment: properties
-                .get("alignment")
-                .map(|alignment| {
-                    let alignment = alignment
-                        .as_map()
-                        .unwrap_or_else(|| panic!("Character '{}' `alignment` is not a map!", id));
-                    let x = alignment
-                        .get("x")
-                        .map(|x| {
-                            x.as_number().unwrap_or_else(|| {
-                                panic!("Character '{}' `alignment.x` is not a number!", id)
-                            })
-                        })
-                        .unwrap_or(0.5) as f32;
-                    let y = alignment
-                        .get("y")
-                        .map(|y| {
-                            y.as_number().unwrap_or_else(|| {
-                                panic!("Character '{}' `alignment.y` is not a number!", id)
-                            })
-                        })
-                        .unwrap_or(0.5) as f32;
-                    (x, y)
-                })
-                .unwrap_or((0.5, 0.5))
-                .into(),
-            effect: properties.get("effect").map(|effect| {
-                effect
-                    .as_text()
-                    .unwrap_or_else(|| panic!("Character '{}' `effect` is not a text!", id))
-                    .to_owned()
-            }),
+            name: properties.name,
+            variants: properties.variants,
+            position: VectorProperty::or(properties.position, 0.0, 0.0),
+            rotation: properties.rotation.unwrap_or_default(),
+            scale: VectorProperty::or(properties.scale, 1.0, 1.0),
+            alignment: VectorProperty::or(properties.alignment, 0.5, 0.5),
+            tint: properties.tint.unwrap_or(Color::WHITE),
+            effect: properties.effect,
         }
     }
 }
 
+#[derive(Debug, Deserialize)]
+struct SceneProperties {
+    background: String,
+    #[serde(default, deserialize_with = "deserialize_color")]
+    tint: Option<Color>,
+    effect: Option<String>,
+}
+
 #[derive(Debug, Clone)]
 pub struct Scene {
     pub background: String,
+    pub tint: Color,
     pub effect: Option<String>,
 }
 
 impl Scene {
     pub fn new(id: &str, properties: &HashMap<String, VnValue>) -> Self {
+        let properties =
+            from_vn_properties::<SceneProperties>(&format!("scene.{}", id), properties)
+                .unwrap_or_else(|error| panic!("Invalid scene: {}", error));
         Self {
-            background: properties
-                .get("background")
-                .map(|background| {
-                    background
-                        .as_text()
-                        .unwrap_or_else(|| panic!("Scene '{}' `background` is not a text!", id))
-                })
-                .unwrap_or_else(|| panic!("Scene '{}' does not have `background` property!", id))
-                .to_owned(),
-            effect: properties.get("effect").map(|effect| {
-                effect
-                    .as_text()
-                    .unwrap_or_else(|| panic!("Scene '{}' `effect` is not a text!", id))
-                    .to_owned()
-            }),
+            background: properties.background,
+            tint: properties.tint.unwrap_or(Color::WHITE),
+            effect: properties.effect,
         }
     }
 }
@@ -221,6 +162,8 @@ pub struct Transition<T> {
     pub duration: f64,
     #[allow(clippy::type_complexity)]
     pub easing: Option<fn(f64, f64, f64, f64) -> f64>,
+    /// Token of suspended action that waits for this transition to complete.
+    pub token: Option<VnToken>,
 }
 
 impl<T> Default for Transition<T> {
@@ -231,6 +174,7 @@ impl<T> Default for Transition<T> {
             time: 0.0,
             duration: 0.0,
             easing: None,
+            token: None,
         }
     }
 }
@@ -249,19 +193,33 @@ impl<T> Transition<T> {
     pub fn is_complete(&self) -> bool {
         self.time >= self.duration - 1.0e-6
     }
+
+    pub fn resolve(&mut self) -> Option<VnToken> {
+        if self.is_complete() {
+            self.token.take()
+        } else {
+            None
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct CharacterTransition {
     pub character: String,
     pub variant: String,
+    /// Overrides character tint.
+    pub tint: Option<Color>,
 }
 
 #[derive(Debug)]
 pub struct DialogTransition {
+    /// Stable dialogue line id, for looking up translations or voice.
+    pub id: Option<String>,
     pub character: Option<String>,
     pub text: String,
     pub choices: Vec<String>,
+    /// Text color, screen decides when not set.
+    pub color: Option<Color>,
 }
 
 #[derive(Debug, Default)]
@@ -272,20 +230,145 @@ pub struct Border {
     pub bottom: f32,
 }
 
+/// Font files of text styles, missing variants fall back to regular one.
+#[derive(Debug, Default, Clone)]
+pub struct FontFamily {
+    pub regular: String,
+    pub bold: Option<String>,
+    pub italic: Option<String>,
+    pub bold_italic: Option<String>,
+}
+
+impl FontFamily {
+    /// Font file of style and whether bold has to be faked.
+    fn select(&self, style: &VnTextStyle) -> (&str, bool) {
+        match (style.bold, style.italic) {
+            (true, true) => {
+                if let Some(asset) = &self.bold_italic {
+                    (asset, false)
+                } else if let Some(asset) = &self.bold {
+                    (asset, false)
+                } else {
+                    (self.italic.as_deref().unwrap_or(&self.regular), true)
+                }
+            }
+            (true, false) => self
+                .bold
+                .as_deref()
+                .map(|asset| (asset, false))
+                .unwrap_or((&self.regular, true)),
+            (false, true) => (self.italic.as_deref().unwrap_or(&self.regular), false),
+            (false, false) => (&self.regular, false),
+        }
+    }
+}
+
+/// Piece of styled text placed in line.
+struct TextPiece {
+    renderable: Text,
+    x: f32,
+    size: f32,
+    color: Color,
+    fake_bold: bool,
+}
+
+#[derive(Default)]
+struct TextLine {
+    pieces: Vec<TextPiece>,
+    /// Width without trailing whitespace.
+    width: f32,
+    /// Advance including trailing whitespace.
+    advance: f32,
+    height: f32,
+}
+
+/// Markup text broken into lines that fit given width.
+struct TextLayout {
+    lines: Vec<TextLine>,
+    size: Vec2<f32>,
+}
+
+impl TextLayout {
+    fn new(
+        ctx: &mut TetraContext,
+        globals: &mut Globals,
+        font: &FontFamily,
+        size: f32,
+        text: &VnMarkup,
+        width: f32,
+        color: Color,
+    ) -> Self {
+        let mut lines = vec![TextLine {
+            height: size * LINE_SPACING,
+            ..Default::default()
+        }];
+        for span in &text.spans {
+            let VnSpan::Text { text, style, .. } = span else {
+                continue;
+            };
+            let (font_asset, fake_bold) = font.select(style);
+            let size = style.size.map(|size| size as f32).unwrap_or(size);
+            let color = style
+                .color
+                .and_then(|color| value_to_color(&VnValue::Color(color)))
+                .unwrap_or(color);
+            let font = globals.font(ctx, font_asset, size);
+            let space_width = measure(ctx, "i i", &font) - measure(ctx, "ii", &font);
+            for (index, paragraph) in text.split('\n').enumerate() {
+                if index > 0 {
+                    lines.push(TextLine::default());
+                }
+                for word in paragraph.split_inclusive(' ') {
+                    let trimmed = word.trim_end_matches(' ');
+                    let word_width = measure(ctx, trimmed, &font);
+                    let advance = word_width + (word.len() - trimmed.len()) as f32 * space_width;
+                    let mut line = lines.last_mut().unwrap();
+                    if !line.pieces.is_empty()
+                        && !trimmed.is_empty()
+                        && line.advance + word_width > width
+                    {
+                        lines.push(TextLine::default());
+                        line = lines.last_mut().unwrap();
+                    }
+                    if !trimmed.is_empty() {
+                        line.width = line.advance + word_width;
+                    }
+                    line.pieces.push(TextPiece {
+                        renderable: Text::new(word, font.clone()),
+                        x: line.advance,
+                        size,
+                        color,
+                        fake_bold,
+                    });
+                    line.advance += advance;
+                    line.height = line.height.max(size * LINE_SPACING);
+                }
+            }
+        }
+        let size = Vec2::new(
+            lines.iter().map(|line| line.width).fold(0.0, f32::max),
+            lines.iter().map(|line| line.height).sum(),
+        );
+        Self { lines, size }
+    }
+}
+
 #[derive(Debug)]
 pub enum RenderCommand {
     Image {
         texture_asset: String,
         region: Rectangle,
         border: Option<Border>,
+        color: Color,
         visibility: f32,
     },
     Text {
-        font_asset: String,
+        font: FontFamily,
         size: f32,
-        text: String,
+        text: VnMarkup,
         region: Rectangle,
         alignment: Vec2<f32>,
+        color: Color,
         visibility: f32,
     },
 }
@@ -297,6 +380,7 @@ impl RenderCommand {
                 texture_asset,
                 region,
                 border,
+                color,
                 visibility,
             } => {
                 let texture = if let Some(texture) = globals.textures.get(&texture_asset) {
@@ -327,7 +411,7 @@ impl RenderCommand {
                         region.height,
                         DrawParams {
                             position,
-                            color: Color::WHITE.with_alpha(visibility),
+                            color: fade(color, visibility),
                             ..Default::default()
                         },
                     )
@@ -338,53 +422,74 @@ impl RenderCommand {
                             position,
                             scale: Vec2::new(region.width, region.height)
                                 / Vec2::new(size.0 as f32, size.1 as f32),
-                            color: Color::WHITE.with_alpha(visibility),
+                            color: fade(color, visibility),
                             ..Default::default()
                         },
                     );
                 }
             }
             RenderCommand::Text {
-                font_asset,
+                font,
                 size,
                 text,
                 region,
                 alignment,
+                color,
                 visibility,
             } => {
-                let font = if let Some(font) = globals.fonts.get(&font_asset) {
-                    font.borrow().data.clone()
-                } else {
-                    let mut font = Font::vector(ctx, &font_asset, size)
-                        .unwrap_or_else(|_| panic!("Could not load `{}` font!", &font_asset));
-                    font.set_filter_mode(ctx, FilterMode::Linear);
+                // Rebuilt only when text or its style changes, like when reveal shows more of it.
+                let key = format!("{:?}@{}@{}@{:?}@{}", font, size, region.width, color, text);
+                if !globals.text_layouts.contains_key(&key) {
+                    let layout =
+                        TextLayout::new(ctx, globals, &font, size, &text, region.width, color);
                     globals
-                        .fonts
-                        .insert(font_asset.to_owned(), Resource::new(font.clone()).into());
-                    font
-                };
-                let position = region.top_left();
+                        .text_layouts
+                        .insert(key.to_owned(), Resource::new(layout).into());
+                }
+                let mut layout = globals.text_layouts[&key].borrow_mut();
+                layout.heartbeat();
                 let container_size = Vec2::new(region.width, region.height);
-                let mut renderable = Text::new(text.as_str(), font);
-                renderable.set_max_width(Some(region.width));
-                let size = renderable
-                    .get_bounds(ctx)
-                    .map(|rect| Vec2::new(rect.width, rect.height))
-                    .unwrap_or_default();
-                let position = Vec2::lerp(position, position + container_size - size, alignment);
-                renderable.draw(
-                    ctx,
-                    DrawParams {
-                        position,
-                        color: Color::WHITE.with_alpha(visibility),
-                        ..Default::default()
-                    },
-                );
+                let text_size = layout.data.size;
+                let position = region.top_left();
+                let mut position =
+                    Vec2::lerp(position, position + container_size - text_size, alignment);
+                for line in &mut layout.data.lines {
+                    let offset = (text_size.x - line.width) * alignment.x;
+                    for piece in &mut line.pieces {
+                        // Roughly aligns baselines of mixed font sizes.
+                        let baseline = (line.height - piece.size * LINE_SPACING) * 0.8;
+                        let params = DrawParams {
+                            position: position + Vec2::new(offset + piece.x, baseline),
+                            color: fade(piece.color, visibility),
+                            ..Default::default()
+                        };
+                        if piece.fake_bold {
+                            let thickness = (piece.size / 32.0).max(1.0);
+                            piece.renderable.draw(
+                                ctx,
+                                DrawParams {
+                                    position: params.position + Vec2::new(thickness, 0.0),
+                                    ..params.clone()
+                                },
+                            );
+                        }
+                        piece.renderable.draw(ctx, params);
+                    }
+                    position.y += line.height;
+                }
             }
         }
     }
 }
 
+/// Width of text rendered in given font.
+fn measure(ctx: &mut TetraContext, text: &str, font: &Font) -> f32 {
+    Text::new(text, font.clone())
+        .get_bounds(ctx)
+        .map(|rect| rect.x + rect.width)
+        .unwrap_or_default()
+}
+
 pub struct Globals {
     pub configs: HashMap<String, Reference>,
     pub screens: Vec<Screen>,
@@ -392,35 +497,87 @@ pub struct Globals {
     pub scenes: HashMap<String, Scene>,
     pub textures: HashMap<String, RefCell<Resource<Texture, 10>>>,
     pub fonts: HashMap<String, RefCell<Resource<Font, 10>>>,
+    /// Laid out texts of render commands, dropped soon after they stop being drawn.
+    text_layouts: HashMap<String, RefCell<Resource<TextLayout, 1>>>,
     pub character_transitions: Vec<Transition<CharacterTransition>>,
     pub scene_transition: Transition<String>,
     pub dialog_transition: Transition<DialogTransition>,
+    pub text_speed: VnTextSpeed,
+    /// Seconds of typewriter reveal of current dialog line, runs once its transition completes.
+    pub dialog_reveal_time: f64,
     pub mouse_position: Vec2<f32>,
     pub clicked: bool,
-    pub(crate) is_dialog_blocked: bool,
+    pub(crate) dialog_token: Option<VnToken>,
+    /// Tokens of transitions and dialog lines replaced before they completed, resolved with
+    /// next update so actions waiting for them do not stay suspended.
+    pub(crate) replaced_tokens: Vec<VnToken>,
     render_commands: Vec<RenderCommand>,
     camera: Camera,
 }
 
+impl Default for Globals {
+    fn default() -> Self {
+        Self {
+            configs: Default::default(),
+            screens: Default::default(),
+            characters: Default::default(),
+            scenes: Default::default(),
+            textures: Default::default(),
+            fonts: Default::default(),
+            text_layouts: Default::default(),
+            character_transitions: Default::default(),
+            scene_transition: Default::default(),
+            dialog_transition: Default::default(),
+            text_speed: Default::default(),
+            dialog_reveal_time: 0.0,
+            mouse_position: Default::default(),
+            clicked: false,
+            dialog_token: None,
+            replaced_tokens: Default::default(),
+            render_commands: Default::default(),
+            camera: Camera::new(0.0, 0.0),
+        }
+    }
+}
+
 impl Globals {
-    pub fn unblock_dialog(&mut self) {
-        if self.dialog_transition.is_complete() {
-            self.is_dialog_blocked = false;
+    /// First call while dialog line is still being revealed only completes the reveal.
+    pub fn unblock_dialog(&mut self) -> Option<VnToken> {
+        if !self.dialog_transition.is_complete() {
+            return None;
+        }
+        let duration = self.dialog_reveal_duration();
+        if self.dialog_reveal_time < duration {
+            self.dialog_reveal_time = duration;
+            return None;
         }
+        self.dialog_token.take()
+    }
+
+    /// Seconds it takes to reveal current dialog line.
+    pub fn dialog_reveal_duration(&self) -> f64 {
+        self.dialog_transition
+            .to
+            .as_ref()
+            .map(|to| VnMarkup::parse(&to.text).duration(&self.text_speed))
+            .unwrap_or_default()
     }
 
     pub fn draw(&mut self, command: RenderCommand) {
         self.render_commands.push(command);
     }
 
-    fn in_progress(&self) -> bool {
-        self.is_dialog_blocked
-            || self
-                .character_transitions
-                .iter()
-                .any(|transition| !transition.is_complete())
-            || !self.scene_transition.is_complete()
-            || !self.dialog_transition.is_complete()
+    /// Vector font of given size, cached per size.
+    fn font(&mut self, ctx: &mut TetraContext, asset: &str, size: f32) -> Font {
+        let key = format!("{}@{}", asset, size);
+        if let Some(font) = self.fonts.get(&key) {
+            return font.borrow().data.clone();
+        }
+        let mut font = Font::vector(ctx, asset, size)
+            .unwrap_or_else(|_| panic!("Could not load `{}` font!", asset));
+        font.set_filter_mode(ctx, FilterMode::Linear);
+        self.fonts.insert(key, Resource::new(font.clone()).into());
+        font
     }
 
     fn manage_assets_loading(&mut self, ctx: &mut TetraContext) {
@@ -518,17 +675,41 @@ impl Globals {
         for key in to_remove {
             self.fonts.remove(&key);
         }
+
+        let to_remove = self
+            .text_layouts
+            .iter_mut()
+            .filter_map(|(key, value)| {
+                let mut value = value.borrow_mut();
+                value.time_left -= delta_time;
+                if value.time_left <= 0.0 {
+                    Some(key.to_owned())
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        for key in to_remove {
+            self.text_layouts.remove(&key);
+        }
     }
 
-    fn update_transitions(&mut self, delta_time: f64) {
+    fn update_transitions(&mut self, delta_time: f64) -> Vec<VnToken> {
+        let mut resolved = std::mem::take(&mut self.replaced_tokens);
+        if self.dialog_transition.is_complete() {
+            self.dialog_reveal_time += delta_time;
+        }
         self.dialog_transition.update(delta_time);
+        resolved.extend(self.dialog_transition.resolve());
         self.scene_transition.update(delta_time);
+        resolved.extend(self.scene_transition.resolve());
         let to_remove = self
             .character_transitions
             .iter_mut()
             .enumerate()
             .filter_map(|(index, transition)| {
                 transition.update(delta_time);
+                resolved.extend(transition.resolve());
                 if transition.to.is_none() && transition.is_complete() {
                     Some(index)
                 } else {
@@ -539,6 +720,7 @@ impl Globals {
         for index in to_remove.into_iter().rev() {
             self.character_transitions.remove(index);
         }
+        resolved
     }
 
     fn update_inputs(&mut self, ctx: &mut TetraContext) {
@@ -572,15 +754,25 @@ impl Globals {
                     .unwrap_or(true)
             })
             .and_then(|name| self.scenes.get(name))
-            .and_then(|scene| Some(self.textures.get(&scene.background)?.borrow_mut()));
+            .and_then(|scene| {
+                Some((
+                    scene.tint,
+                    self.textures.get(&scene.background)?.borrow_mut(),
+                ))
+            });
         let to = self
             .scene_transition
             .to
             .as_ref()
             .and_then(|name| self.scenes.get(name))
-            .and_then(|scene| Some(self.textures.get(&scene.background)?.borrow_mut()));
+            .and_then(|scene| {
+                Some((
+                    scene.tint,
+                    self.textures.get(&scene.background)?.borrow_mut(),
+                ))
+            });
         let factor = self.scene_transition.sample() as f32;
-        if let Some(mut texture) = from {
+        if let Some((tint, mut texture)) = from {
             let origin = Vec2 {
                 x: texture.data.width() as f32 * 0.5,
                 y: texture.data.height() as f32 * 0.5,
@@ -590,12 +782,12 @@ impl Globals {
                 ctx,
                 DrawParams {
                     origin,
-                    color: Color::WHITE.with_alpha(1.0 - factor),
+                    color: fade(tint, 1.0 - factor),
                     ..Default::default()
                 },
             );
         }
-        if let Some(mut texture) = to {
+        if let Some((tint, mut texture)) = to {
             let origin = Vec2 {
                 x: texture.data.width() as f32 * 0.5,
                 y: texture.data.height() as f32 * 0.5,
@@ -605,7 +797,7 @@ impl Globals {
                 ctx,
                 DrawParams {
                     origin,
-                    color: Color::WHITE.with_alpha(factor),
+                    color: fade(tint, factor),
                     ..Default::default()
                 },
             );
@@ -627,6 +819,7 @@ impl Globals {
                     character.scale,
                     character.rotation,
                     character.alignment,
+                    transition.tint.unwrap_or(character.tint),
                     self.textures.get(variant)?.borrow_mut(),
                 ))
             });
@@ -638,12 +831,13 @@ impl Globals {
                     character.scale,
                     character.rotation,
                     character.alignment,
+                    transition.tint.unwrap_or(character.tint),
                     self.textures.get(variant)?.borrow_mut(),
                 ))
             });
             let factor = character_transition.sample() as f32;
             let camera_region = self.camera.visible_rect();
-            if let Some((position, scale, rotation, alignment, mut texture)) = from {
+            if let Some((position, scale, rotation, alignment, tint, mut texture)) = from {
                 let position = Vec2 {
                     x: camera_region.width * position.x * 0.5,
                     y: camera_region.height * position.y * 0.5,
@@ -660,11 +854,11 @@ impl Globals {
                         rotation,
                         scale,
                         origin,
-                        color: Color::WHITE.with_alpha(1.0 - factor),
+                        color: fade(tint, 1.0 - factor),
                     },
                 );
             }
-            if let Some((position, scale, rotation, alignment, mut texture)) = to {
+            if let Some((position, scale, rotation, alignment, tint, mut texture)) = to {
                 let position = Vec2 {
                     x: camera_region.width * position.x * 0.5,
                     y: camera_region.height * position.y * 0.5,
@@ -681,7 +875,7 @@ impl Globals {
                         rotation,
                         scale,
                         origin,
-                        color: Color::WHITE.with_alpha(factor),
+                        color: fade(tint, factor),
                     },
                 );
             }
@@ -699,6 +893,14 @@ pub struct GameState {
     pub vm: Vm,
     pub desired_width: f32,
     pub desired_height: f32,
+    debug_listener: Option<TcpListener>,
+    debug_connections: Vec<VnDebugConnection<TcpStream>>,
+    debug_stdio: Option<VnDebugConnection<VnStdio>>,
+    /// Story texts, used when switching language back to original one.
+    original_texts: VnLocale,
+    language: Option<String>,
+    /// Infinite loop got already reported, so it is not logged every frame.
+    stuck: bool,
 }
 
 impl GameState {
@@ -710,6 +912,11 @@ impl GameState {
         desired_height: f32,
     ) -> Self {
         vm.enter(entry, None);
+        let original_texts = VnLocale::template(&story);
+        let language = vm
+            .localization()
+            .language()
+            .map(|language| language.to_owned());
         let (context, registry) = vm.host_mut().context_and_registry();
         let configs = story
             .configs
@@ -728,6 +935,8 @@ impl GameState {
             .iter()
             .map(|(id, character)| (id.to_owned(), Character::new(id, &character.properties)))
             .collect();
+        let text_speed = VnTextSpeed::from_story(&story)
+            .unwrap_or_else(|error| panic!("Invalid dialogue config: {}", error));
         let scenes = story
             .scenes
             .iter()
@@ -737,25 +946,107 @@ impl GameState {
             GAME_GLOBALS,
             Globals {
                 configs,
-                screens: Default::default(),
                 characters,
                 scenes,
-                textures: Default::default(),
-                fonts: Default::default(),
-                character_transitions: Default::default(),
-                scene_transition: Default::default(),
-                dialog_transition: Default::default(),
-                mouse_position: Default::default(),
-                clicked: false,
-                is_dialog_blocked: false,
-                render_commands: Default::default(),
-                camera: Camera::new(0.0, 0.0),
+                text_speed,
+                ..Default::default()
             },
         );
         Self {
             vm,
             desired_width,
             desired_height,
+            debug_listener: None,
+            debug_connections: Default::default(),
+            debug_stdio: None,
+            original_texts,
+            language,
+            stuck: false,
+        }
+    }
+
+    pub fn debugger(mut self, listener: Option<TcpListener>) -> Self {
+        self.debug_listener = listener;
+        self
+    }
+
+    /// Takes debugger requests from standard input and writes responses to standard output.
+    pub fn debugger_stdio(mut self, enabled: bool) -> Self {
+        self.debug_stdio = enabled.then(|| VnDebugConnection::new(VnStdio::default()));
+        self
+    }
+
+    fn update_debugger(&mut self) {
+        if let Some(listener) = self.debug_listener.as_ref() {
+            while let Ok((stream, _)) = listener.accept() {
+                if stream.set_nonblocking(true).is_ok() {
+                    self.debug_connections.push(VnDebugConnection::new(stream));
+                }
+            }
+        }
+        let vm = &mut self.vm;
+        self.debug_connections
+            .retain_mut(|connection| connection.process(vm).unwrap_or_default());
+        if let Some(connection) = self.debug_stdio.as_mut() {
+            if !connection.process(vm).unwrap_or_default() {
+                self.debug_stdio = None;
+            }
+        }
+    }
+
+    /// Translates currently displayed dialogue line when language gets changed.
+    fn update_language(&mut self) {
+        let language = self
+            .vm
+            .localization()
+            .language()
+            .map(|language| language.to_owned());
+        if language == self.language {
+            return;
+        }
+        self.language = language;
+        let context = self.vm.host_mut().context();
+        let (id, choices) = match context
+            .custom::<Globals>(GAME_GLOBALS)
+            .unwrap()
+            .dialog_transition
+            .to
+            .as_ref()
+        {
+            Some(DialogTransition {
+                id: Some(id),
+                choices,
+                ..
+            }) => (id.to_owned(), choices.len()),
+            _ => return,
+        };
+        let (what, choices) = {
+            let localization = context.custom::<VnLocalization>(VN_LOCALIZATION).unwrap();
+            let text = |id: String| {
+                localization
+                    .text(&id)
+                    .or_else(|| self.original_texts.text(&id))
+            };
+            let what = text(VnTextId::format(&id, VnAction::WHAT_PARAM, None))
+                .map(|text| format_markup_with_globals(context, text));
+            let choices = (0..choices)
+                .map(|index| {
+                    text(VnTextId::format(&id, VnAction::CHOICES_PARAM, Some(index)))
+                        .map(|text| format_with_globals(context, text))
+                })
+                .collect::<Vec<_>>();
+            (what, choices)
+        };
+        let globals = context.custom_mut::<Globals>(GAME_GLOBALS).unwrap();
+        if let Some(to) = globals.dialog_transition.to.as_mut() {
+            if let Some(what) = what {
+                to.text = what;
+            }
+            for (choice, text) in to.choices.iter_mut().zip(choices) {
+                if let Some(text) = text {
+                    *choice = text;
+                }
+            }
         }
     }
 
@@ -807,11 +1098,24 @@ impl State for GameState {
             .unwrap();
         globals.manage_assets_loading(ctx);
         globals.manage_assets_lifetime(delta_time);
-        globals.update_transitions(delta_time);
+        let resolved = globals.update_transitions(delta_time);
         globals.update_inputs(ctx);
-        if !globals.in_progress() {
-            self.vm.step();
+        let clicked = globals.clicked;
+        for token in resolved {
+            self.vm.resolve(token);
+        }
+        self.vm.advance_time(delta_time);
+        if clicked {
+            self.vm.skip_waits();
+        }
+        self.update_debugger();
+        // Looping story yields until next frame, so host can still change its state.
+        let stuck = self.vm.run(STEPS_PER_FRAME) == VnRunResult::InfiniteLoop;
+        if stuck && !self.stuck {
+            eprintln!("Story got stuck in infinite loop!");
         }
+        self.stuck = stuck;
+        self.update_language();
         Ok(())
     }
 
@@ -1,13 +1,15 @@
-use super::easing;
-use crate::game_state::{CharacterTransition, Globals, Transition, GAME_GLOBALS};
+use super::{easing, suspend, transition_token};
+use crate::game_state::{value_to_color, CharacterTransition, Globals, Transition, GAME_GLOBALS};
 use intuicio_essentials::{core as intuicio_core, data as intuicio_data, prelude::*};
-use vngineer_core::script::*;
+use vngineer_core::{action::*, script::*};
 
+#[allow(clippy::too_many_arguments)]
 #[intuicio_function(module_name = "vn_character", use_context)]
 fn show(
     context: &mut Context,
     character: VnValue,
     variant: VnValue,
+    tint: VnValue,
     duration: VnValue,
     ease_in: VnValue,
     ease_out: VnValue,
@@ -15,8 +17,10 @@ fn show(
 ) -> VnResult {
     let character = character.as_text().expect("`character` is not a text!");
     let variant = variant.as_text().unwrap_or("default");
+    let tint = (!tint.is_none()).then(|| value_to_color(&tint).expect("`tint` is not a color!"));
     let duration = duration.as_number().unwrap_or_default();
     let easing = easing(ease_in, ease_out, ease_in_out);
+    let token = transition_token(context, duration);
     let globals = context.custom_mut::<Globals>(GAME_GLOBALS).unwrap();
     let found = globals.character_transitions.iter().position(|transition| {
         transition
@@ -25,32 +29,26 @@ fn show(
             .map(|to| to.character == character)
             .unwrap_or_default()
     });
-    let transition = if let Some(index) = found {
-        let from = globals.character_transitions.remove(index).to.take();
-        Transition {
-            from,
-            to: Some(CharacterTransition {
-                character: character.to_owned(),
-                variant: variant.to_owned(),
-            }),
-            time: 0.0,
-            duration,
-            easing,
-        }
+    let from = if let Some(index) = found {
+        let mut replaced = globals.character_transitions.remove(index);
+        globals.replaced_tokens.extend(replaced.token.take());
+        replaced.to.take()
     } else {
-        Transition {
-            from: None,
-            to: Some(CharacterTransition {
-                character: character.to_owned(),
-                variant: variant.to_owned(),
-            }),
-            time: 0.0,
-            duration,
-            easing,
-        }
+        None
     };
-    globals.character_transitions.push(transition);
-    VnResult::Continue
+    globals.character_transitions.push(Transition {
+        from,
+        to: Some(CharacterTransition {
+            character: character.to_owned(),
+            variant: variant.to_owned(),
+            tint,
+        }),
+        time: 0.0,
+        duration,
+        easing,
+        token,
+    });
+    suspend(token)
 }
 
 #[intuicio_function(module_name = "vn_character", use_context)]
@@ -65,7 +63,7 @@ fn hide(
     let character = character.as_text().expect("`character` is not a text!");
     let duration = duration.as_number().unwrap_or_default();
     let easing = easing(ease_in, ease_out, ease_in_out);
-    let globals = context.custom_mut::<Globals>(GAME_GLOBALS).unwrap();
+    let globals = context.custom::<Globals>(GAME_GLOBALS).unwrap();
     let found = globals.character_transitions.iter().position(|transition| {
         transition
             .to
@@ -73,20 +71,96 @@ fn hide(
             .map(|to| to.character == character)
             .unwrap_or_default()
     });
-    if let Some(index) = found {
-        let from = globals.character_transitions.remove(index).to.take();
-        globals.character_transitions.push(Transition {
-            from,
-            to: None,
-            time: 0.0,
-            duration,
-            easing,
-        });
-    }
-    VnResult::Continue
+    let Some(index) = found else {
+        return VnResult::Continue;
+    };
+    let token = transition_token(context, duration);
+    let globals = context.custom_mut::<Globals>(GAME_GLOBALS).unwrap();
+    let mut replaced = globals.character_transitions.remove(index);
+    globals.replaced_tokens.extend(replaced.token.take());
+    globals.character_transitions.push(Transition {
+        from: replaced.to.take(),
+        to: None,
+        time: 0.0,
+        duration,
+        easing,
+        token,
+    });
+    suspend(token)
 }
 
 pub fn install(registry: &mut Registry) {
-    registry.add_function(show::define_function(registry));
-    registry.add_function(hide::define_function(registry));
+    registry.add_function(
+        VnActionDefinition::new("show")
+            .module_name("vn_character")
+            .doc("Shows character, or changes variant of already shown one.")
+            .param(VnActionParam::new("character").required())
+            .param(VnActionParam::new("variant").doc("Defaults to `default` variant."))
+            .param(VnActionParam::new("tint"))
+            .param(VnActionParam::new("duration").doc("Transition time in seconds."))
+            .param(VnActionParam::new("ease_in"))
+            .param(VnActionParam::new("ease_out"))
+            .param(VnActionParam::new("ease_in_out"))
+            .annotate(show::define_function(registry)),
+    );
+    registry.add_function(
+        VnActionDefinition::new("hide")
+            .module_name("vn_character")
+            .doc("Hides shown character.")
+            .param(VnActionParam::new("character").required())
+            .param(VnActionParam::new("duration").doc("Transition time in seconds."))
+            .param(VnActionParam::new("ease_in"))
+            .param(VnActionParam::new("ease_out"))
+            .param(VnActionParam::new("ease_in_out"))
+            .annotate(hide::define_function(registry)),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vngineer_core::vm::{Globals as VnGlobals, VN_GLOBALS};
+
+    #[test]
+    fn test_overlapping_transitions() {
+        let mut context = Context::new(1024, 1024, 1024);
+        context.set_custom(VN_GLOBALS, VnGlobals::default());
+        context.set_custom(GAME_GLOBALS, Globals::default());
+        let show = |context: &mut Context| {
+            show(
+                context,
+                VnValue::Text("rin".to_owned()),
+                VnValue::None,
+                VnValue::None,
+                VnValue::Number(1.0),
+                VnValue::None,
+                VnValue::None,
+                VnValue::None,
+            )
+        };
+        let VnResult::Suspend(first) = show(&mut context) else {
+            panic!("Show did not suspend!");
+        };
+        let VnResult::Suspend(second) = show(&mut context) else {
+            panic!("Show did not suspend!");
+        };
+        let globals = context.custom::<Globals>(GAME_GLOBALS).unwrap();
+        assert_eq!(globals.replaced_tokens, vec![first]);
+        assert_eq!(globals.character_transitions.len(), 1);
+
+        let VnResult::Suspend(third) = hide(
+            &mut context,
+            VnValue::Text("rin".to_owned()),
+            VnValue::Number(1.0),
+            VnValue::None,
+            VnValue::None,
+            VnValue::None,
+        ) else {
+            panic!("Hide did not suspend!");
+        };
+        let globals = context.custom::<Globals>(GAME_GLOBALS).unwrap();
+        assert_eq!(globals.replaced_tokens, vec![first, second]);
+        assert_eq!(globals.character_transitions.len(), 1);
+        assert_eq!(globals.character_transitions[0].token, Some(third));
+    }
 }
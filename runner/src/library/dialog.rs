@@ -1,71 +1,90 @@
-use super::{easing, GameTransition};
-use crate::game_state::{DialogTransition, Globals, Transition, GAME_GLOBALS};
+use super::{easing, render::color_to_reference, suspend, GameTransition};
+use crate::game_state::{value_to_color, DialogTransition, Globals, Transition, GAME_GLOBALS};
 use intuicio_essentials::{core as intuicio_core, data as intuicio_data, prelude::*};
 use intuicio_frontend_simpleton::prelude::*;
 use vngineer_core::{
+    action::*,
+    format::{format_markup_with_globals, format_with_globals},
+    markup::VnMarkup,
     script::*,
-    vm::{Globals as VnGlobals, VN_GLOBALS},
+    vm::{Globals as VnGlobals, VN_CHOICE_GLOBAL, VN_GLOBALS},
 };
-
-const CHOICE_PROPERTY: &str = "CHOICE";
+use vngineer_simpleton::reference_to_number;
 
 #[allow(clippy::too_many_arguments)]
 #[intuicio_function(module_name = "vn_dialog", use_context)]
 fn say(
     context: &mut Context,
+    id: VnValue,
     who: VnValue,
     what: VnValue,
     choices: VnValue,
+    color: VnValue,
     duration: VnValue,
     ease_in: VnValue,
     ease_out: VnValue,
     ease_in_out: VnValue,
     non_blocking: VnValue,
 ) -> VnResult {
+    let id = id.as_text();
     let who = who.as_text();
-    let what = what.as_text().expect("`what` is not a text!");
-    let choices = choices.as_array();
+    let what = format_markup_with_globals(context, what.as_text().expect("`what` is not a text!"));
+    let choices = choices
+        .as_array()
+        .map(|choices| {
+            choices
+                .iter()
+                .enumerate()
+                .map(|(index, choice)| {
+                    let choice = choice
+                        .as_text()
+                        .unwrap_or_else(|| panic!("`choices[{}]` is not a text!", index));
+                    format_with_globals(context, choice)
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    let color =
+        (!color.is_none()).then(|| value_to_color(&color).expect("`color` is not a color!"));
     let duration = duration.as_number().unwrap_or_default();
     let easing = easing(ease_in, ease_out, ease_in_out);
     let non_blocking = non_blocking.as_boolean().unwrap_or_default();
+    let token = if non_blocking {
+        None
+    } else {
+        let globals = context.custom_mut::<VnGlobals>(VN_GLOBALS).unwrap();
+        Some(globals.new_token())
+    };
     let globals = context.custom_mut::<Globals>(GAME_GLOBALS).unwrap();
     let from = globals.dialog_transition.to.take();
+    globals.replaced_tokens.extend(globals.dialog_token.take());
     globals.dialog_transition = Transition {
         from,
         to: Some(DialogTransition {
+            id: id.map(|id| id.to_owned()),
             character: who.map(|name| name.to_owned()),
-            text: what.to_owned(),
-            choices: choices
-                .map(|choices| {
-                    choices
-                        .iter()
-                        .enumerate()
-                        .map(|(index, choice)| {
-                            choice
-                                .as_text()
-                                .unwrap_or_else(|| panic!("`choices[{}]` is not a text!", index))
-                                .to_owned()
-                        })
-                        .collect()
-                })
-                .unwrap_or_default(),
+            text: what,
+            choices,
+            color,
         }),
         time: 0.0,
         duration,
         easing,
+        token: None,
     };
-    if !non_blocking {
-        globals.is_dialog_blocked = true;
-    }
-    VnResult::Continue
+    globals.dialog_token = token;
+    globals.dialog_reveal_time = 0.0;
+    suspend(token)
 }
 
 #[derive(IntuicioStruct, Default)]
 #[intuicio(name = "GameDialogTransition", module_name = "vn")]
 pub struct GameDialogTransition {
+    pub id: Reference,
     pub character: Reference,
     pub text: Reference,
     pub choices: Reference,
+    pub color: Reference,
 }
 
 #[intuicio_function(module_name = "dialog", use_context, use_registry)]
@@ -80,6 +99,11 @@ fn transition(context: &Context, registry: &Registry) -> Reference {
                 .map(|from| {
                     Reference::new(
                         GameDialogTransition {
+                            id: from
+                                .id
+                                .as_ref()
+                                .map(|id| Reference::new_text(id.to_owned(), registry))
+                                .unwrap_or_default(),
                             character: from
                                 .character
                                 .as_ref()
@@ -99,6 +123,10 @@ fn transition(context: &Context, registry: &Registry) -> Reference {
                                     registry,
                                 )
                             },
+                            color: from
+                                .color
+                                .map(|color| color_to_reference(color, registry))
+                                .unwrap_or_default(),
                         },
                         registry,
                     )
@@ -111,6 +139,11 @@ fn transition(context: &Context, registry: &Registry) -> Reference {
                 .map(|to| {
                     Reference::new(
                         GameDialogTransition {
+                            id: to
+                                .id
+                                .as_ref()
+                                .map(|id| Reference::new_text(id.to_owned(), registry))
+                                .unwrap_or_default(),
                             character: to
                                 .character
                                 .as_ref()
@@ -130,6 +163,10 @@ fn transition(context: &Context, registry: &Registry) -> Reference {
                                     registry,
                                 )
                             },
+                            color: to
+                                .color
+                                .map(|color| color_to_reference(color, registry))
+                                .unwrap_or_default(),
                         },
                         registry,
                     )
@@ -143,46 +180,121 @@ fn transition(context: &Context, registry: &Registry) -> Reference {
 
 #[intuicio_function(module_name = "dialog", use_context)]
 fn complete(context: &mut Context, choice: Reference) -> Reference {
-    {
+    let token = {
         let globals = context.custom_mut::<Globals>(GAME_GLOBALS).unwrap();
-        globals.unblock_dialog();
+        globals.unblock_dialog()
+    };
+    let globals = context.custom_mut::<VnGlobals>(VN_GLOBALS).unwrap();
+    if let Some(token) = token {
+        globals.resolve(token);
     }
     if let Some(choice) = choice.read::<Integer>() {
-        let globals = context.custom_mut::<VnGlobals>(VN_GLOBALS).unwrap();
         globals
             .properties
-            .insert(CHOICE_PROPERTY.to_owned(), VnValue::Number(*choice as _));
+            .insert(VN_CHOICE_GLOBAL.to_owned(), VnValue::Integer(*choice as _));
     }
     Reference::null()
 }
 
-#[intuicio_function(module_name = "dialog", use_registry)]
+/// Revealed or remaining markup of text at percentage of typewriter reveal.
+#[intuicio_function(module_name = "dialog", use_context, use_registry)]
 fn text_fragment(
+    context: &Context,
     registry: &Registry,
     text: Reference,
     percentage: Reference,
     forward: Reference,
 ) -> Reference {
     let text = text.read::<Text>().expect("`text` is not a text!");
-    let percentage = *percentage
-        .read::<Real>()
-        .expect("`percentage` is not a number!");
+    let percentage = reference_to_number(&percentage).expect("`percentage` is not a number!");
     let forward = *forward
         .read::<Boolean>()
         .expect("`forward` is not a boolean!");
-    let length = (text.len() as Real * percentage) as usize;
+    let globals = context.custom::<Globals>(GAME_GLOBALS).unwrap();
+    let (revealed, remaining) =
+        VnMarkup::parse(&text).split_at_factor(percentage, &globals.text_speed);
     if forward {
-        Reference::new_text(text[0..length].to_owned(), registry)
+        Reference::new_text(revealed.to_string(), registry)
     } else {
-        Reference::new_text(text[length..].to_owned(), registry)
+        Reference::new_text(remaining.to_string(), registry)
     }
 }
 
+/// `{revealed, remaining, complete}` markup of text after seconds of typewriter reveal.
+#[intuicio_function(module_name = "dialog", use_context, use_registry)]
+fn text_reveal(
+    context: &Context,
+    registry: &Registry,
+    text: Reference,
+    time: Reference,
+) -> Reference {
+    let text = text.read::<Text>().expect("`text` is not a text!");
+    let time = reference_to_number(&time).expect("`time` is not a number!");
+    let globals = context.custom::<Globals>(GAME_GLOBALS).unwrap();
+    let (revealed, remaining) = VnMarkup::parse(&text).split_at_time(time, &globals.text_speed);
+    let complete = remaining.plain_text().is_empty();
+    Reference::new_map(
+        [
+            (
+                "revealed".to_owned(),
+                Reference::new_text(revealed.to_string(), registry),
+            ),
+            (
+                "remaining".to_owned(),
+                Reference::new_text(remaining.to_string(), registry),
+            ),
+            (
+                "complete".to_owned(),
+                Reference::new_boolean(complete, registry),
+            ),
+        ]
+        .into_iter()
+        .collect(),
+        registry,
+    )
+}
+
+/// Seconds of typewriter reveal of current dialog line.
+#[intuicio_function(module_name = "dialog", use_context, use_registry)]
+fn reveal_time(context: &Context, registry: &Registry) -> Reference {
+    let globals = context.custom::<Globals>(GAME_GLOBALS).unwrap();
+    Reference::new_real(globals.dialog_reveal_time, registry)
+}
+
+/// Changes typewriter reveal speed, in graphemes per second.
+#[intuicio_function(module_name = "dialog", use_context)]
+fn set_text_speed(context: &mut Context, chars_per_second: Reference) -> Reference {
+    let chars_per_second =
+        reference_to_number(&chars_per_second).expect("`chars_per_second` is not a number!");
+    let globals = context.custom_mut::<Globals>(GAME_GLOBALS).unwrap();
+    globals.text_speed.chars_per_second = chars_per_second;
+    Reference::null()
+}
+
 pub fn install(registry: &mut Registry) {
-    registry.add_function(say::define_function(registry));
+    registry.add_function(
+        VnActionDefinition::new("say")
+            .module_name("vn_dialog")
+            .doc("Shows dialog line and waits for player to continue.")
+            .param(VnActionParam::new(VnAction::WHO_PARAM).doc("Speaking character."))
+            .param(
+                VnActionParam::new(VnAction::WHAT_PARAM)
+                    .required()
+                    .doc("Text of line."),
+            )
+            .param(
+                VnActionParam::new(VnAction::CHOICES_PARAM)
+                    .doc("Player picks one into `CHOICE` global."),
+            )
+            .param(VnActionParam::new("non_blocking").default(VnValue::Boolean(false)))
+            .annotate(say::define_function(registry)),
+    );
 
     registry.add_struct(GameDialogTransition::define_struct(registry));
     registry.add_function(transition::define_function(registry));
     registry.add_function(complete::define_function(registry));
     registry.add_function(text_fragment::define_function(registry));
+    registry.add_function(text_reveal::define_function(registry));
+    registry.add_function(reveal_time::define_function(registry));
+    registry.add_function(set_text_speed::define_function(registry));
 }
@@ -8,10 +8,31 @@ use easer::functions::*;
 use intuicio_essentials::{core as intuicio_core, data as intuicio_data, prelude::*};
 use intuicio_frontend_simpleton::prelude::*;
 use tetra::graphics::Rectangle;
-use vngineer_core::script::*;
+use vngineer_core::{
+    script::*,
+    vm::{Globals as VnGlobals, VN_GLOBALS},
+};
+use vngineer_simpleton::reference_to_number;
 
 use crate::game_state::{Globals, GAME_GLOBALS};
 
+/// Creates token for transitions that take time, so VM waits for them to complete.
+pub fn transition_token(context: &mut Context, duration: f64) -> Option<VnToken> {
+    if duration > 0.0 {
+        let globals = context.custom_mut::<VnGlobals>(VN_GLOBALS).unwrap();
+        Some(globals.new_token())
+    } else {
+        None
+    }
+}
+
+pub fn suspend(token: Option<VnToken>) -> VnResult {
+    match token {
+        Some(token) => VnResult::Suspend(token),
+        None => VnResult::Continue,
+    }
+}
+
 #[allow(clippy::type_complexity)]
 pub fn easing(
     ease_in: VnValue,
@@ -94,18 +115,10 @@ fn config(context: &Context, name: Reference) -> Reference {
 fn hover(context: &Context, registry: &Registry, region: Reference) -> Reference {
     let region = region.read::<Array>().expect("`region` is not an array!");
     let region = Rectangle::new(
-        *region[0]
-            .read::<Real>()
-            .expect("`region[0]` is not a number!") as f32,
-        *region[1]
-            .read::<Real>()
-            .expect("`region[1]` is not a number!") as f32,
-        *region[2]
-            .read::<Real>()
-            .expect("`region[2]` is not a number!") as f32,
-        *region[3]
-            .read::<Real>()
-            .expect("`region[3]` is not a number!") as f32,
+        reference_to_number(&region[0]).expect("`region[0]` is not a number!") as f32,
+        reference_to_number(&region[1]).expect("`region[1]` is not a number!") as f32,
+        reference_to_number(&region[2]).expect("`region[2]` is not a number!") as f32,
+        reference_to_number(&region[3]).expect("`region[3]` is not a number!") as f32,
     );
     let globals = context.custom::<Globals>(GAME_GLOBALS).unwrap();
     Reference::new_boolean(region.contains_point(globals.mouse_position), registry)
@@ -1,7 +1,87 @@
-use crate::game_state::{Border, Globals, RenderCommand, GAME_GLOBALS};
+use crate::game_state::{Border, FontFamily, Globals, RenderCommand, GAME_GLOBALS};
 use intuicio_essentials::{core as intuicio_core, data as intuicio_data, prelude::*};
 use intuicio_frontend_simpleton::prelude::*;
-use tetra::{graphics::Rectangle, math::Vec2};
+use tetra::{
+    graphics::{Color, Rectangle},
+    math::Vec2,
+};
+use vngineer_core::markup::VnMarkup;
+use vngineer_simpleton::reference_to_number;
+
+/// Color as `{r, g, b, a}` map with channels in 0-1 range.
+pub fn color_to_reference(color: Color, registry: &Registry) -> Reference {
+    Reference::new_map(
+        [
+            (
+                "r".to_owned(),
+                Reference::new_real(color.r as Real, registry),
+            ),
+            (
+                "g".to_owned(),
+                Reference::new_real(color.g as Real, registry),
+            ),
+            (
+                "b".to_owned(),
+                Reference::new_real(color.b as Real, registry),
+            ),
+            (
+                "a".to_owned(),
+                Reference::new_real(color.a as Real, registry),
+            ),
+        ]
+        .into_iter()
+        .collect(),
+        registry,
+    )
+}
+
+/// Null means white.
+pub fn reference_to_color(color: &Reference) -> Color {
+    if color.is_null() {
+        return Color::WHITE;
+    }
+    let color = color.read::<Map>().expect("`color` is not a map!");
+    let channel = |name: &str| {
+        color
+            .get(name)
+            .map(|value| {
+                reference_to_number(value)
+                    .unwrap_or_else(|| panic!("`color.{}` is not a number!", name))
+                    as f32
+            })
+            .unwrap_or(1.0)
+    };
+    Color::rgba(channel("r"), channel("g"), channel("b"), channel("a"))
+}
+
+/// Either font file or `{regular, bold, italic, bold_italic}` map of font files.
+pub fn reference_to_font_family(font: &Reference) -> FontFamily {
+    if let Some(asset) = font.read::<Text>() {
+        return FontFamily {
+            regular: asset.to_owned(),
+            ..Default::default()
+        };
+    }
+    let font = font
+        .read::<Map>()
+        .expect("`font_asset` is not a text nor a map!");
+    let variant = |name: &str| {
+        font.get(name)
+            .filter(|value| !value.is_null())
+            .map(|value| {
+                value
+                    .read::<Text>()
+                    .unwrap_or_else(|| panic!("`font_asset.{}` is not a text!", name))
+                    .to_owned()
+            })
+    };
+    FontFamily {
+        regular: variant("regular").expect("`font_asset.regular` is missing!"),
+        bold: variant("bold"),
+        italic: variant("italic"),
+        bold_italic: variant("bold_italic"),
+    }
+}
 
 #[intuicio_function(module_name = "render", use_context)]
 fn draw_image(
@@ -9,6 +89,7 @@ fn draw_image(
     texture_asset: Reference,
     region: Reference,
     border: Reference,
+    color: Reference,
     visibility: Reference,
 ) -> Reference {
     let texture_asset = texture_asset
@@ -17,46 +98,32 @@ fn draw_image(
         .to_owned();
     let region = region.read::<Array>().expect("`region` is not an array!");
     let region = Rectangle::new(
-        *region[0]
-            .read::<Real>()
-            .expect("`region[0]` is not a number!") as f32,
-        *region[1]
-            .read::<Real>()
-            .expect("`region[1]` is not a number!") as f32,
-        *region[2]
-            .read::<Real>()
-            .expect("`region[2]` is not a number!") as f32,
-        *region[3]
-            .read::<Real>()
-            .expect("`region[3]` is not a number!") as f32,
+        reference_to_number(&region[0]).expect("`region[0]` is not a number!") as f32,
+        reference_to_number(&region[1]).expect("`region[1]` is not a number!") as f32,
+        reference_to_number(&region[2]).expect("`region[2]` is not a number!") as f32,
+        reference_to_number(&region[3]).expect("`region[3]` is not a number!") as f32,
     );
     let border = border.read::<Array>().map(|array| Border {
-        left: *array[0]
-            .read::<Real>()
-            .expect("`region[0]` is not a number!") as f32,
-        right: *array[1]
-            .read::<Real>()
-            .expect("`region[1]` is not a number!") as f32,
-        top: *array[1]
-            .read::<Real>()
-            .expect("`region[2]` is not a number!") as f32,
-        bottom: *array[1]
-            .read::<Real>()
-            .expect("`region[3]` is not a number!") as f32,
+        left: reference_to_number(&array[0]).expect("`region[0]` is not a number!") as f32,
+        right: reference_to_number(&array[1]).expect("`region[1]` is not a number!") as f32,
+        top: reference_to_number(&array[1]).expect("`region[2]` is not a number!") as f32,
+        bottom: reference_to_number(&array[1]).expect("`region[3]` is not a number!") as f32,
     });
-    let visibility = *visibility
-        .read::<Real>()
-        .expect("`visibility` is nto a number!") as f32;
+    let color = reference_to_color(&color);
+    let visibility =
+        reference_to_number(&visibility).expect("`visibility` is not a number!") as f32;
     let globals = context.custom_mut::<Globals>(GAME_GLOBALS).unwrap();
     globals.draw(RenderCommand::Image {
         texture_asset,
         region,
         border,
+        color,
         visibility,
     });
     Reference::null()
 }
 
+#[allow(clippy::too_many_arguments)]
 #[intuicio_function(module_name = "render", use_context)]
 fn draw_text(
     context: &mut Context,
@@ -65,53 +132,37 @@ fn draw_text(
     text: Reference,
     region: Reference,
     alignment: Reference,
+    color: Reference,
     visibility: Reference,
 ) -> Reference {
-    let font_asset = font_asset
-        .read::<Text>()
-        .expect("`font_asset` is not a text!")
-        .to_owned();
-    let size = *size.read::<Real>().expect("`size` is not a number!") as f32;
-    let text = text
-        .read::<Text>()
-        .expect("`text` is not a text!")
-        .to_owned();
+    let font = reference_to_font_family(&font_asset);
+    let size = reference_to_number(&size).expect("`size` is not a number!") as f32;
+    let text = VnMarkup::parse(&text.read::<Text>().expect("`text` is not a text!"));
     let region = region.read::<Array>().expect("`region` is not an array!");
     let region = Rectangle::new(
-        *region[0]
-            .read::<Real>()
-            .expect("`region[0]` is not a number!") as f32,
-        *region[1]
-            .read::<Real>()
-            .expect("`region[1]` is not a number!") as f32,
-        *region[2]
-            .read::<Real>()
-            .expect("`region[2]` is not a number!") as f32,
-        *region[3]
-            .read::<Real>()
-            .expect("`region[3]` is not a number!") as f32,
+        reference_to_number(&region[0]).expect("`region[0]` is not a number!") as f32,
+        reference_to_number(&region[1]).expect("`region[1]` is not a number!") as f32,
+        reference_to_number(&region[2]).expect("`region[2]` is not a number!") as f32,
+        reference_to_number(&region[3]).expect("`region[3]` is not a number!") as f32,
     );
     let alignment = alignment
         .read::<Array>()
         .expect("`alignment` is not an array!");
     let alignment = Vec2::new(
-        *alignment[0]
-            .read::<Real>()
-            .expect("`alignment[0]` is not a number!") as f32,
-        *alignment[1]
-            .read::<Real>()
-            .expect("`alignment[1]` is not a number!") as f32,
+        reference_to_number(&alignment[0]).expect("`alignment[0]` is not a number!") as f32,
+        reference_to_number(&alignment[1]).expect("`alignment[1]` is not a number!") as f32,
     );
-    let visibility = *visibility
-        .read::<Real>()
-        .expect("`visibility` is nto a number!") as f32;
+    let color = reference_to_color(&color);
+    let visibility =
+        reference_to_number(&visibility).expect("`visibility` is not a number!") as f32;
     let globals = context.custom_mut::<Globals>(GAME_GLOBALS).unwrap();
     globals.draw(RenderCommand::Text {
-        font_asset,
+        font,
         size,
         text,
         region,
         alignment,
+        color,
         visibility,
     });
     Reference::null()
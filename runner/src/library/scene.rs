@@ -1,7 +1,7 @@
-use super::easing;
+use super::{easing, suspend, transition_token};
 use crate::game_state::{Globals, Transition, GAME_GLOBALS};
 use intuicio_essentials::{core as intuicio_core, data as intuicio_data, prelude::*};
-use vngineer_core::script::*;
+use vngineer_core::{action::*, script::*};
 
 #[intuicio_function(module_name = "vn_scene", use_context)]
 fn scene(
@@ -15,8 +15,12 @@ fn scene(
     let name = name.as_text().expect("`name` is not a text!");
     let duration = duration.as_number().unwrap_or_default();
     let easing = easing(ease_in, ease_out, ease_in_out);
+    let token = transition_token(context, duration);
     let globals = context.custom_mut::<Globals>(GAME_GLOBALS).unwrap();
     let from = globals.scene_transition.to.take();
+    globals
+        .replaced_tokens
+        .extend(globals.scene_transition.token.take());
     let to = if globals.scenes.contains_key(name) {
         Some(name.to_owned())
     } else {
@@ -28,10 +32,21 @@ fn scene(
         time: 0.0,
         duration,
         easing,
+        token,
     };
-    VnResult::Continue
+    suspend(token)
 }
 
 pub fn install(registry: &mut Registry) {
-    registry.add_function(scene::define_function(registry));
+    registry.add_function(
+        VnActionDefinition::new("scene")
+            .module_name("vn_scene")
+            .doc("Changes background scene.")
+            .param(VnActionParam::new("name").required())
+            .param(VnActionParam::new("duration").doc("Transition time in seconds."))
+            .param(VnActionParam::new("ease_in"))
+            .param(VnActionParam::new("ease_out"))
+            .param(VnActionParam::new("ease_in_out"))
+            .annotate(scene::define_function(registry)),
+    );
 }
@@ -1,6 +1,6 @@
 use crate::game_state::{Globals, Screen, GAME_GLOBALS};
 use intuicio_essentials::{core as intuicio_core, data as intuicio_data, prelude::*};
-use vngineer_core::script::*;
+use vngineer_core::{action::*, script::*};
 
 #[intuicio_function(module_name = "vn_screen", use_context)]
 fn show_screen(context: &mut Context, name: VnValue, module_name: VnValue) -> VnResult {
@@ -37,6 +37,20 @@ fn hide_screen(context: &mut Context, name: VnValue, module_name: VnValue) -> Vn
 }
 
 pub fn install(registry: &mut Registry) {
-    registry.add_function(show_screen::define_function(registry));
-    registry.add_function(hide_screen::define_function(registry));
+    registry.add_function(
+        VnActionDefinition::new("show_screen")
+            .module_name("vn_screen")
+            .doc("Shows Simpleton screen on top of story.")
+            .param(VnActionParam::new("name").required())
+            .param(VnActionParam::new("module_name").required())
+            .annotate(show_screen::define_function(registry)),
+    );
+    registry.add_function(
+        VnActionDefinition::new("hide_screen")
+            .module_name("vn_screen")
+            .doc("Hides all instances of Simpleton screen.")
+            .param(VnActionParam::new("name").required())
+            .param(VnActionParam::new("module_name").required())
+            .annotate(hide_screen::define_function(registry)),
+    );
 }
@@ -8,16 +8,102 @@ use crate::game_state::GameState;
 use clap::Parser;
 use intuicio_essentials::prelude::*;
 use intuicio_frontend_simpleton::prelude::*;
-use std::path::PathBuf;
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, LineWriter},
+    net::TcpListener,
+    path::PathBuf,
+};
 use tetra::{time::Timestep, ContextBuilder};
 use vngineer_core::prelude::*;
 
+const DEFAULT_DEBUG_ADDRESS: &str = "127.0.0.1:7878";
+const DEBUG_STDIO: &str = "stdio";
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+struct ApplicationConfig {
+    title: String,
+    width: i32,
+    height: i32,
+    desired_width: f32,
+    desired_height: f32,
+    fullscreen: bool,
+    fps: f64,
+    entry: String,
+}
+
+impl Default for ApplicationConfig {
+    fn default() -> Self {
+        Self {
+            title: "vngineer".to_owned(),
+            width: 1024,
+            height: 768,
+            desired_width: 1024.0,
+            desired_height: 768.0,
+            fullscreen: false,
+            fps: 30.0,
+            entry: "start".to_owned(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct LocalizationConfig {
+    /// Locale table paths by language.
+    tables: HashMap<String, String>,
+    language: Option<String>,
+}
+
 #[derive(Debug, Parser)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
     /// Input cartridge file path.
     #[arg(value_name = "PATH")]
     entry: String,
+
+    /// Listen for story debugger connections on given address.
+    /// Bare port or no value at all listens on loopback only,
+    /// `stdio` takes requests from standard input and responds on standard output.
+    #[arg(
+        long,
+        value_name = "ADDRESS",
+        num_args = 0..=1,
+        default_missing_value = DEFAULT_DEBUG_ADDRESS
+    )]
+    debug: Option<String>,
+
+    /// Write trace of every executed story action to given file.
+    #[arg(long, value_name = "PATH")]
+    trace: Option<String>,
+
+    /// Print coverage report of given trace files instead of running the game.
+    #[arg(long, value_name = "PATH", num_args = 1..)]
+    coverage: Vec<String>,
+
+    /// Print story flow graph in given format (`dot` or `json`) instead of running the game.
+    #[arg(long, value_name = "FORMAT")]
+    graph: Option<String>,
+
+    /// Print every dialogue line in given format (`csv`, `json` or `locale` table template)
+    /// instead of running the game.
+    #[arg(long, value_name = "FORMAT")]
+    dialogue: Option<String>,
+
+    /// Print all routes reachable from entry chapter instead of running the game.
+    #[arg(long)]
+    explore: bool,
+
+    /// Maximum number of choices in explored route.
+    #[arg(long, value_name = "COUNT", default_value_t = 32)]
+    explore_depth: usize,
+
+    /// Print every action available to story scripts instead of running the game.
+    #[arg(long)]
+    actions: bool,
 }
 
 fn main() -> tetra::Result {
@@ -33,10 +119,10 @@ fn main() -> tetra::Result {
     crate::library::install(&mut registry);
 
     let mut vn_content_provider = ExtensionContentProvider::<VnFile>::default()
-        .extension("vns", FileContentProvider::new("vns", VnContentParser))
-        .extension("plugin", IgnoreContentProvider)
-        .extension("simp", IgnoreContentProvider)
-        .extension("bimp", IgnoreContentProvider)
+        .extension(
+            "vns",
+            VnGlobContentProvider::new(FileContentProvider::new("vns", VnContentParser)),
+        )
         .default_extension("vns");
     let vn_package = VnPackage::new(&cli.entry, &mut vn_content_provider).unwrap();
     vn_package.install_plugins(&mut registry, &[root.as_str()]);
@@ -49,124 +135,154 @@ fn main() -> tetra::Result {
         .extension("bimp", SimpletonBinaryFileContentProvider::new("bimp"))
         .extension("plugin", IgnoreContentProvider)
         .default_extension("simp");
-    for (path, file) in &vn_package.files {
-        let mut directory = PathBuf::from(path);
-        directory.pop();
-        for path in &file.dependencies {
-            let entry = directory.join(path).to_string_lossy().to_string();
-            let simpleton_package =
-                SimpletonPackage::new(&entry, &mut simpleton_content_provider).unwrap();
-            simpleton_package.install_plugins(&mut registry, &[root.as_str()]);
-            simpleton_package
-                .compile()
-                .install::<VmScope<SimpletonScriptExpression>>(&mut registry, None);
+    for dependency in vn_package.dependencies_of(VnDependencyKind::Simpleton) {
+        let simpleton_package =
+            SimpletonPackage::new(&dependency.path, &mut simpleton_content_provider).unwrap();
+        simpleton_package.install_plugins(&mut registry, &[root.as_str()]);
+        simpleton_package
+            .compile()
+            .install::<VmScope<SimpletonScriptExpression>>(&mut registry, None);
+    }
+
+    if cli.actions {
+        for action in VnActionInfo::all(&registry) {
+            println!("{}", action);
         }
+        return Ok(());
     }
 
     let story = vn_package.compile();
+    if let Err(errors) = story
+        .validate()
+        .and_then(|_| story.validate_actions(&registry))
+    {
+        for error in errors {
+            eprintln!("{}", error);
+        }
+        panic!("Story is not valid!");
+    }
+
+    if !cli.coverage.is_empty() {
+        let mut records = vec![];
+        for path in &cli.coverage {
+            let file =
+                File::open(path).unwrap_or_else(|_| panic!("Could not open trace file: {}", path));
+            records.extend(
+                read_trace(BufReader::new(file)).unwrap_or_else(|error| {
+                    panic!("Could not read trace file {}: {}", path, error)
+                }),
+            );
+        }
+        print!("{}", VnCoverageReport::new(&story, &records));
+        return Ok(());
+    }
+
+    if let Some(format) = cli.graph.as_deref() {
+        let graph = VnStoryGraph::new(&story);
+        match format {
+            "dot" => print!("{}", graph.to_dot()),
+            "json" => println!("{}", graph.to_json()),
+            _ => panic!("Unknown graph format: {}", format),
+        }
+        return Ok(());
+    }
+
+    if let Some(format) = cli.dialogue.as_deref() {
+        let script = VnDialogueScript::new(&story);
+        match format {
+            "csv" => print!("{}", script.to_csv()),
+            "json" => println!("{}", script.to_json()),
+            "locale" => print!("{}", VnLocale::template(&story)),
+            _ => panic!("Unknown dialogue format: {}", format),
+        }
+        return Ok(());
+    }
+
     let host = Host::new(Context::new(10240, 10240, 0), registry.into());
     let mut vm = Vm::new(host);
-    vm.add_story(&story);
+    vm.add_story(&story).expect("Story got already validated!");
+    if let Some(config) = story.configs.get("localization") {
+        let mut locale_content_provider = ExtensionContentProvider::<VnLocale>::default()
+            .extension(
+                "locale",
+                FileContentProvider::new("locale", VnLocaleContentParser),
+            )
+            .default_extension("locale");
+        let config =
+            from_vn_properties::<LocalizationConfig>("config.localization", &config.properties)
+                .unwrap_or_else(|error| panic!("Invalid config: {}", error));
+        for (language, path) in config.tables {
+            let path = PathBuf::from(&root)
+                .join(path)
+                .to_string_lossy()
+                .to_string();
+            vm.localization()
+                .load(&language, &path, &mut locale_content_provider)
+                .unwrap_or_else(|error| panic!("Could not load locale table {}: {}", path, error));
+        }
+        if let Some(language) = config.language.as_deref() {
+            vm.set_language(Some(language));
+        }
+    }
+    if let Some(path) = cli.trace.as_ref() {
+        let file =
+            File::create(path).unwrap_or_else(|_| panic!("Could not create trace file: {}", path));
+        vm.set_tracer(VnTraceWriter::new(LineWriter::new(file)));
+    }
 
-    let (title, width, height, desired_width, desired_height, fullscreen, fps, entry) = story
+    let application = story
         .configs
         .get("application")
         .map(|config| {
-            let title = config
-                .properties
-                .get("title")
-                .map(|title| title.as_text().expect("`title` is not a text!"))
-                .unwrap_or("vngineer");
-            let width = config
-                .properties
-                .get("width")
-                .map(|width| width.as_number().expect("`width` is not a number!") as _)
-                .unwrap_or(1024);
-            let height = config
-                .properties
-                .get("height")
-                .map(|height| height.as_number().expect("`height` is not a number!") as _)
-                .unwrap_or(768);
-            let desired_width = config
-                .properties
-                .get("desired_width")
-                .map(|desired_width| {
-                    desired_width
-                        .as_number()
-                        .expect("`desired_width` is not a number!") as f32
-                })
-                .unwrap_or(1024.0);
-            let desired_height = config
-                .properties
-                .get("desired_height")
-                .map(|desired_height| {
-                    desired_height
-                        .as_number()
-                        .expect("`desired_height` is not a number!") as f32
-                })
-                .unwrap_or(768.0);
-            let fullscreen = config
-                .properties
-                .get("fullscreen")
-                .map(|fullscreen| {
-                    fullscreen
-                        .as_boolean()
-                        .expect("`fullscreen` is not a number!")
-                })
-                .unwrap_or(false);
-            let fps = config
-                .properties
-                .get("fps")
-                .map(|fps| fps.as_number().expect("`fps` is not a number!"))
-                .unwrap_or(30.0);
-            let entry = config
-                .properties
-                .get("entry")
-                .map(|entry| entry.as_text().expect("`entry` is not a text!"))
-                .unwrap_or("start")
-                .to_owned();
-            (
-                title,
-                width,
-                height,
-                desired_width,
-                desired_height,
-                fullscreen,
-                fps,
-                entry,
-            )
+            from_vn_properties::<ApplicationConfig>("config.application", &config.properties)
+                .unwrap_or_else(|error| panic!("Invalid config: {}", error))
         })
-        .unwrap_or_else(|| {
-            (
-                "vngineer",
-                1024,
-                768,
-                1024.0,
-                768.0,
-                false,
-                30.0,
-                "start".to_owned(),
-            )
-        });
+        .unwrap_or_default();
+    let entry = application.entry;
+
+    if cli.explore {
+        let report = VnExplorer::default()
+            .max_depth(cli.explore_depth)
+            .explore(VnHarness::new(&story), &entry)
+            .unwrap_or_else(|error| panic!("Could not explore story: {}", error));
+        print!("{}", report);
+        return Ok(());
+    }
+
+    let debug_stdio = cli.debug.as_deref() == Some(DEBUG_STDIO);
+    let debug_listener = cli.debug.as_ref().filter(|_| !debug_stdio).map(|address| {
+        let address = match address.parse::<u16>() {
+            Ok(port) => format!("127.0.0.1:{}", port),
+            Err(_) => address.to_owned(),
+        };
+        let listener = TcpListener::bind(&address)
+            .unwrap_or_else(|_| panic!("Could not listen for debugger on: {}", address));
+        listener
+            .set_nonblocking(true)
+            .expect("Could not make debugger listener non-blocking!");
+        listener
+    });
 
     let mut root = PathBuf::from(&cli.entry);
     root.pop();
     let _ = std::env::set_current_dir(root);
 
-    ContextBuilder::new(title, width, height)
-        .fullscreen(fullscreen)
+    ContextBuilder::new(application.title, application.width, application.height)
+        .fullscreen(application.fullscreen)
         .show_mouse(true)
         .quit_on_escape(false)
         .resizable(true)
-        .timestep(Timestep::Fixed(fps))
+        .timestep(Timestep::Fixed(application.fps))
         .build()?
         .run(|_| {
             Ok(GameState::new(
                 vm,
                 story,
                 &entry,
-                desired_width,
-                desired_height,
-            ))
+                application.desired_width,
+                application.desired_height,
+            )
+            .debugger(debug_listener)
+            .debugger_stdio(debug_stdio))
         })
 }
@@ -17,13 +17,78 @@ struct VnResultEnter {
 #[derive(IntuicioStruct, Default)]
 struct VnResultExit;
 
+#[derive(IntuicioStruct, Default)]
+struct VnResultSpawn {
+    pub thread: Reference,
+    pub chapter: Reference,
+    pub label: Reference,
+}
+
+#[derive(IntuicioStruct, Default)]
+struct VnResultKill {
+    pub thread: Reference,
+}
+
+#[derive(IntuicioStruct, Default)]
+struct VnResultJoin {
+    pub thread: Reference,
+}
+
+#[derive(IntuicioStruct, Default)]
+struct VnResultWait;
+
+#[derive(IntuicioStruct, Default)]
+struct VnResultSuspend {
+    pub token: Reference,
+}
+
 pub fn value_to_reference(value: &VnValue, registry: &Registry) -> Reference {
     match value {
         VnValue::None => Reference::null(),
         VnValue::Boolean(value) => Reference::new_boolean(*value, registry),
+        VnValue::Integer(value) => Reference::new_integer(*value, registry),
         VnValue::Number(value) => Reference::new_real(*value, registry),
         VnValue::Text(value) => Reference::new_text(value.to_owned(), registry),
-        VnValue::Color(value) => Reference::new_integer(*value as Integer, registry),
+        VnValue::Color(_) => {
+            let (r, g, b, a) = value.as_rgba_normalized().unwrap();
+            Reference::new_map(
+                [
+                    ("r".to_owned(), Reference::new_real(r, registry)),
+                    ("g".to_owned(), Reference::new_real(g, registry)),
+                    ("b".to_owned(), Reference::new_real(b, registry)),
+                    ("a".to_owned(), Reference::new_real(a, registry)),
+                ]
+                .into_iter()
+                .collect(),
+                registry,
+            )
+        }
+        VnValue::Vec2 { x, y } => Reference::new_map(
+            [
+                ("x".to_owned(), Reference::new_real(*x, registry)),
+                ("y".to_owned(), Reference::new_real(*y, registry)),
+            ]
+            .into_iter()
+            .collect(),
+            registry,
+        ),
+        VnValue::Rect {
+            x,
+            y,
+            width,
+            height,
+        } => Reference::new_map(
+            [
+                ("x".to_owned(), Reference::new_real(*x, registry)),
+                ("y".to_owned(), Reference::new_real(*y, registry)),
+                ("width".to_owned(), Reference::new_real(*width, registry)),
+                ("height".to_owned(), Reference::new_real(*height, registry)),
+            ]
+            .into_iter()
+            .collect(),
+            registry,
+        ),
+        VnValue::Reference { name, .. } => Reference::new_text(name.to_owned(), registry),
         VnValue::Array(value) => Reference::new_array(
             value
                 .iter()
@@ -41,6 +106,37 @@ pub fn value_to_reference(value: &VnValue, registry: &Registry) -> Reference {
     }
 }
 
+/// Reads either integer or real, since story values keep integers apart from numbers.
+pub fn reference_to_number(value: &Reference) -> Option<Real> {
+    value
+        .read::<Real>()
+        .map(|value| *value)
+        .or_else(|| value.read::<Integer>().map(|value| *value as Real))
+}
+
+pub fn reference_to_value(value: &Reference) -> VnValue {
+    if let Some(value) = value.read::<Boolean>() {
+        VnValue::Boolean(*value)
+    } else if let Some(value) = value.read::<Integer>() {
+        VnValue::Integer(*value)
+    } else if let Some(value) = value.read::<Real>() {
+        VnValue::Number(*value)
+    } else if let Some(value) = value.read::<Text>() {
+        VnValue::Text(value.to_owned())
+    } else if let Some(value) = value.read::<Array>() {
+        VnValue::Array(value.iter().map(reference_to_value).collect())
+    } else if let Some(value) = value.read::<Map>() {
+        VnValue::Map(
+            value
+                .iter()
+                .map(|(key, value)| (key.to_owned(), reference_to_value(value)))
+                .collect(),
+        )
+    } else {
+        VnValue::None
+    }
+}
+
 #[intuicio_function(module_name = "vn", use_context, use_registry)]
 pub fn simpleton(
     context: &mut Context,
@@ -95,15 +191,220 @@ pub fn simpleton(
         VnResult::Enter { chapter, label }
     } else if result.read::<VnResultExit>().is_some() {
         VnResult::Exit
+    } else if let Some(result) = result.read::<VnResultSpawn>() {
+        let thread = result
+            .thread
+            .read::<Text>()
+            .expect("`thread` is not a text!")
+            .to_owned();
+        let chapter = result.chapter.read::<Text>().map(|value| value.to_owned());
+        let label = result.label.read::<Text>().map(|value| value.to_owned());
+        VnResult::Spawn {
+            thread,
+            chapter,
+            label,
+        }
+    } else if let Some(result) = result.read::<VnResultKill>() {
+        let thread = result
+            .thread
+            .read::<Text>()
+            .expect("`thread` is not a text!")
+            .to_owned();
+        VnResult::Kill { thread }
+    } else if let Some(result) = result.read::<VnResultJoin>() {
+        let thread = result
+            .thread
+            .read::<Text>()
+            .expect("`thread` is not a text!")
+            .to_owned();
+        VnResult::Join { thread }
+    } else if result.read::<VnResultWait>().is_some() {
+        VnResult::Wait
+    } else if let Some(result) = result.read::<VnResultSuspend>() {
+        let token = *result
+            .token
+            .read::<Integer>()
+            .expect("`token` is not an integer!");
+        VnResult::Suspend(VnToken(token as _))
     } else {
         VnResult::Continue
     };
     result
 }
 
+#[intuicio_function(module_name = "vn", use_context, use_registry)]
+pub fn new_token(context: &mut Context, registry: &Registry) -> Reference {
+    let globals = context
+        .custom_mut::<Globals>(VN_GLOBALS)
+        .expect("Cannot access VN globals!");
+    Reference::new_integer(globals.new_token().0 as Integer, registry)
+}
+
+#[intuicio_function(module_name = "vn", use_context)]
+pub fn resolve(context: &mut Context, token: Reference) -> Reference {
+    let token = *token.read::<Integer>().expect("`token` is not an integer!");
+    let globals = context
+        .custom_mut::<Globals>(VN_GLOBALS)
+        .expect("Cannot access VN globals!");
+    globals.resolve(VnToken(token as _));
+    Reference::null()
+}
+
+#[intuicio_function(module_name = "vn", use_context)]
+pub fn watch_global(context: &mut Context, name: Reference) -> Reference {
+    let name = name.read::<Text>().expect("`name` is not a text!");
+    let globals = context
+        .custom_mut::<Globals>(VN_GLOBALS)
+        .expect("Cannot access VN globals!");
+    globals.watched.insert(name.to_owned());
+    Reference::null()
+}
+
+#[intuicio_function(module_name = "vn", use_context)]
+pub fn unwatch_global(context: &mut Context, name: Reference) -> Reference {
+    let name = name.read::<Text>().expect("`name` is not a text!");
+    let globals = context
+        .custom_mut::<Globals>(VN_GLOBALS)
+        .expect("Cannot access VN globals!");
+    globals.watched.remove(name.as_str());
+    Reference::null()
+}
+
+#[intuicio_function(module_name = "vn", use_context, use_registry)]
+pub fn global_changes(context: &Context, registry: &Registry) -> Reference {
+    let globals = context
+        .custom::<Globals>(VN_GLOBALS)
+        .expect("Cannot access VN globals!");
+    Reference::new_array(
+        globals
+            .changes
+            .iter()
+            .map(|change| {
+                Reference::new_map(
+                    [
+                        (
+                            "name".to_owned(),
+                            Reference::new_text(change.name.to_owned(), registry),
+                        ),
+                        ("old".to_owned(), value_to_reference(&change.old, registry)),
+                        ("new".to_owned(), value_to_reference(&change.new, registry)),
+                    ]
+                    .into_iter()
+                    .collect(),
+                    registry,
+                )
+            })
+            .collect(),
+        registry,
+    )
+}
+
+#[intuicio_function(module_name = "vn", use_context, use_registry)]
+pub fn language(context: &Context, registry: &Registry) -> Reference {
+    let localization = context
+        .custom::<VnLocalization>(VN_LOCALIZATION)
+        .expect("Cannot access VN localization!");
+    localization
+        .language()
+        .map(|language| Reference::new_text(language.to_owned(), registry))
+        .unwrap_or_default()
+}
+
+#[intuicio_function(module_name = "vn", use_context, use_registry)]
+pub fn languages(context: &Context, registry: &Registry) -> Reference {
+    let localization = context
+        .custom::<VnLocalization>(VN_LOCALIZATION)
+        .expect("Cannot access VN localization!");
+    let mut languages = localization.locales.keys().collect::<Vec<_>>();
+    languages.sort();
+    Reference::new_array(
+        languages
+            .into_iter()
+            .map(|language| Reference::new_text(language.to_owned(), registry))
+            .collect(),
+        registry,
+    )
+}
+
+#[intuicio_function(module_name = "vn", use_context)]
+pub fn select_language(context: &mut Context, language: Reference) -> Reference {
+    let language = language.read::<Text>();
+    let localization = context
+        .custom_mut::<VnLocalization>(VN_LOCALIZATION)
+        .expect("Cannot access VN localization!");
+    localization.set_language(language.as_deref().map(|language| language.as_str()));
+    Reference::null()
+}
+
+/// Formats message pattern with given arguments map, or with story globals when it is null.
+#[intuicio_function(module_name = "vn", use_context, use_registry)]
+pub fn format(
+    context: &Context,
+    registry: &Registry,
+    pattern: Reference,
+    arguments: Reference,
+) -> Reference {
+    let pattern = pattern.read::<Text>().expect("`pattern` is not a text!");
+    let result = if arguments.is_null() {
+        format_with_globals(context, pattern.as_str())
+    } else {
+        let arguments = match reference_to_value(&arguments) {
+            VnValue::Map(arguments) => arguments,
+            _ => panic!("`arguments` is not a map!"),
+        };
+        let language = context
+            .custom::<VnLocalization>(VN_LOCALIZATION)
+            .and_then(|localization| localization.language());
+        format_message(pattern.as_str(), &arguments, language)
+    };
+    Reference::new_text(result, registry)
+}
+
 pub fn install(registry: &mut Registry) {
     registry.add_struct(VnResultJumpTo::define_struct(registry));
     registry.add_struct(VnResultEnter::define_struct(registry));
     registry.add_struct(VnResultExit::define_struct(registry));
+    registry.add_struct(VnResultSpawn::define_struct(registry));
+    registry.add_struct(VnResultKill::define_struct(registry));
+    registry.add_struct(VnResultJoin::define_struct(registry));
+    registry.add_struct(VnResultWait::define_struct(registry));
+    registry.add_struct(VnResultSuspend::define_struct(registry));
     registry.add_function(simpleton::define_function(registry));
+    registry.add_function(new_token::define_function(registry));
+    registry.add_function(resolve::define_function(registry));
+    registry.add_function(watch_global::define_function(registry));
+    registry.add_function(unwatch_global::define_function(registry));
+    registry.add_function(global_changes::define_function(registry));
+    registry.add_function(language::define_function(registry));
+    registry.add_function(languages::define_function(registry));
+    registry.add_function(select_language::define_function(registry));
+    registry.add_function(format::define_function(registry));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_numbers() {
+        let mut registry = Registry::default();
+        intuicio_frontend_simpleton::library::install(&mut registry);
+        let mut content_provider = ExtensionContentProvider::<VnFile>::default()
+            .extension("vns", FileContentProvider::new("vns", VnContentParser))
+            .default_extension("vns");
+        let story = VnPackage::new("../resources/main.vns", &mut content_provider)
+            .unwrap()
+            .compile();
+        let style = &story.configs["style"].properties;
+        let font_size = value_to_reference(&style["font_size"], &registry);
+        assert!(font_size.read::<Integer>().is_some());
+        assert_eq!(reference_to_number(&font_size), Some(32.0));
+        let sentence_pause = value_to_reference(
+            &story.configs["dialogue"].properties["sentence_pause"],
+            &registry,
+        );
+        assert_eq!(reference_to_number(&sentence_pause), Some(0.3));
+        let text = value_to_reference(&style["font"], &registry);
+        assert_eq!(reference_to_number(&text), None);
+    }
 }